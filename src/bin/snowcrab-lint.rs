@@ -0,0 +1,144 @@
+//!
+//! Check maps in the `map.txt` format before playing them, for instance in a
+//! pre-commit hook. The problems are printed as `path:line:column: message`.
+//! The exit code is 0 if no map has errors, 1 otherwise, and 2 if a map cannot
+//! be read or the arguments are wrong.
+//!
+
+#![allow(clippy::needless_return)]
+
+use std::path::PathBuf;
+#[cfg(feature = "solver")]
+use std::time::Duration;
+
+use snowcrab::lint::{self, Severity};
+#[cfg(feature = "solver")]
+use snowcrab::solver::Limits;
+
+const USAGE: &str = r#"Usage: snowcrab-lint [OPTIONS] [MAP...]
+
+Check the maps, map.txt if none is given.
+
+Options:
+  --no-solve            Do not look for a solution of each level, the solver being slow.
+  --solve-secs <S>      Give up looking for the solution of a level after S seconds. [default: 10]
+  --strict              Fail on warnings too.
+  -h, --help            Print this help.
+  -V, --version         Print the version.
+
+Exit code: 0 if the maps are fine, 1 if they have errors, 2 if they cannot be read.
+"#;
+
+/// The default map to check, the one embedded in the game.
+const DEFAULT_MAP_PATH: &str = "map.txt";
+
+struct Options {
+	maps: Vec<PathBuf>,
+	solve: bool,
+	solve_secs: u64,
+	strict: bool,
+}
+
+fn main() {
+	let options = match parse(std::env::args().skip(1)) {
+		Ok(Some(options)) => options,
+		Ok(None) => return,
+		Err(e) => {
+			eprintln!("{e}\n\n{USAGE}");
+			std::process::exit(2);
+		}
+	};
+
+	let mut failed = false;
+	for path in &options.maps {
+		let map = match std::fs::read_to_string(path) {
+			Ok(map) => map,
+			Err(e) => {
+				eprintln!("{}: cannot read the map: {e}", path.display());
+				std::process::exit(2);
+			}
+		};
+		let diagnostics = lint::lint(&map);
+		#[cfg(feature = "solver")]
+		let diagnostics = with_solutions(diagnostics, &map, &options);
+		for diagnostic in &diagnostics {
+			println!("{}:{diagnostic}", path.display());
+			failed |= match diagnostic.severity {
+				Severity::Error => true,
+				Severity::Warning => options.strict,
+				Severity::Note => false,
+			};
+		}
+	}
+	if failed {
+		std::process::exit(1);
+	}
+}
+
+/// Add the problems the solver finds to those of the map, if it has no error
+/// which would make them pointless.
+#[cfg(feature = "solver")]
+fn with_solutions(mut diagnostics: Vec<lint::Diagnostic>, map: &str, options: &Options) -> Vec<lint::Diagnostic> {
+	if options.solve && !diagnostics.iter().any(|d| d.severity == Severity::Error) {
+		let limits = Limits {
+			max_duration: Some(Duration::from_secs(options.solve_secs)),
+			..Limits::default()
+		};
+		diagnostics.extend(lint::lint_solutions(map, &limits));
+	}
+	return diagnostics;
+}
+
+/// Parse the arguments, without the program's name. `None` if there is
+/// nothing left to do.
+fn parse<I: IntoIterator<Item = String>>(args: I) -> Result<Option<Options>, String> {
+	let mut options = Options {
+		maps: Vec::new(),
+		solve: true,
+		solve_secs: 10,
+		strict: false,
+	};
+	let mut args = args.into_iter();
+	while let Some(arg) = args.next() {
+		let (name, mut inline_value) = match arg.split_once('=') {
+			Some((name, value)) if name.starts_with("--") => (name.to_owned(), Some(value.to_owned())),
+			_ => (arg, None),
+		};
+		let mut value = || {
+			inline_value
+				.take()
+				.or_else(|| args.next())
+				.ok_or_else(|| format!("Missing value for {name}."))
+		};
+
+		match name.as_str() {
+			"--no-solve" => options.solve = false,
+			"--solve-secs" if !cfg!(feature = "solver") => {
+				return Err("This build has no solver, see the `solver` feature.".to_owned());
+			}
+			"--solve-secs" => {
+				let v = value()?;
+				options.solve_secs = v.parse().map_err(|_| format!("Invalid number of seconds: {v:?}."))?;
+			}
+			"--strict" => options.strict = true,
+			"-h" | "--help" => {
+				print!("{USAGE}");
+				return Ok(None);
+			}
+			"-V" | "--version" => {
+				println!("snowcrab-lint {}", env!("CARGO_PKG_VERSION"));
+				return Ok(None);
+			}
+			"--" => options.maps.extend(args.by_ref().map(PathBuf::from)),
+			_ if name.starts_with('-') => return Err(format!("Unknown argument: {name:?}.")),
+			_ => options.maps.push(PathBuf::from(&name)),
+		}
+		if inline_value.is_some() {
+			return Err(format!("{name} does not take a value."));
+		}
+	}
+	if options.maps.is_empty() {
+		options.maps.push(PathBuf::from(DEFAULT_MAP_PATH));
+	}
+	return Ok(Some(options));
+}
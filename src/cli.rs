@@ -0,0 +1,402 @@
+//!
+//! Parse the command-line arguments of the game.
+//!
+
+use std::io::{self, BufRead, Write};
+use std::path::PathBuf;
+
+use snowcrab::save::Settings;
+use snowcrab::save::slots::{DEFAULT_SLOT, Slots};
+
+pub const USAGE: &str = r#"Usage: snowcrab [OPTIONS]
+       snowcrab --slots | --copy-slot <from> <to> | --rename-slot <from> <to> | --delete-slot <name>
+
+Options:
+  --map <path>          Play on the map in that file instead of the embedded one.
+  --slot <name>         Play in that save slot, created if needed. Asked at start if there are several.
+  --save <path>         Save file to load at start and write at exit, instead of a slot.
+  --no-save             Neither load nor write any save file.
+  --new-game            Do not load the save file, start from the beginning.
+  --no-snapshot         Save only the history, which is replayed at load, not the board.
+  --verify-save         Replay the history at load, and check the board is the saved one.
+  --autosave-moves <N>  Save every N actions, 0 for never. [default: 20]
+  --autosave-secs <S>   Save every S seconds if the game changed, 0 for never. [default: 60]
+  --tui                 Play in the terminal instead of a window.
+  --edit <path>         Edit the map in that file, created if needed, instead of playing.
+  --replay              Watch the history of the save in a window instead of playing.
+  --export-png <path>   Draw the board of the save in that PNG file instead of playing.
+  --export-gif <path>   Draw the history of the save in that animated GIF instead of playing.
+  --export-tile <px>    Side of a tile in the exported images, in pixels. [default: 32]
+  --window <WxH>        Size of the window in pixels. [default: 1200x800]
+  --fullscreen          Open the window in fullscreen.
+  --fps <N>             Maximum number of frames per second. [default: 10]
+  -h, --help            Print this help.
+  -V, --version         Print the version.
+
+Save slots, in $XDG_DATA_HOME/snowcrab/slots:
+  --slots                    List the save slots.
+  --copy-slot <from> <to>    Copy a slot to a new one.
+  --rename-slot <from> <to>  Rename a slot.
+  --delete-slot <name>       Delete a slot.
+"#;
+
+/// The largest tile in the exported images, in pixels.
+const MAX_EXPORT_TILE: u32 = 256;
+
+/// What the program was asked to do.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Command {
+	Play(Options),
+	Slots(SlotCommand),
+	Help,
+	Version,
+}
+
+/// What to do with the save slots.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum SlotCommand {
+	List,
+	Copy { from: String, to: String },
+	Rename { from: String, to: String },
+	Delete(String),
+}
+
+/// Where the game is saved.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum SaveChoice {
+	/// Neither load nor write any save.
+	Nowhere,
+	File(PathBuf),
+	Slot(String),
+	/// Ask the player which slot to play in, if there are several.
+	Pick,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Options {
+	pub map: Option<PathBuf>,
+	pub save: SaveChoice,
+	pub new_game: bool,
+	/// Save a snapshot of the board with the history.
+	pub snapshot: bool,
+	/// Load by replaying the history, even if there is a snapshot.
+	pub verify_save: bool,
+	/// Save every this many actions, if not 0.
+	pub autosave_actions: usize,
+	/// Save every this many seconds, if not 0.
+	pub autosave_secs: u64,
+	/// Play in the terminal. The default if the game is built without the window.
+	pub tui: bool,
+	/// Edit the map in that file instead of playing.
+	pub edit: Option<PathBuf>,
+	/// Watch the history of the save instead of playing.
+	pub replay: bool,
+	/// Draw the board of the save in that PNG file instead of playing.
+	pub export_png: Option<PathBuf>,
+	/// Draw the history of the save in that GIF file instead of playing.
+	pub export_gif: Option<PathBuf>,
+	/// The side of a tile in the exported images, in pixels.
+	pub export_tile: u32,
+	pub window_size: (u32, u32),
+	pub fullscreen: bool,
+	pub fps: u64,
+}
+
+impl Default for Options {
+	fn default() -> Self {
+		Options {
+			map: None,
+			save: SaveChoice::Pick,
+			new_game: false,
+			snapshot: true,
+			verify_save: false,
+			autosave_actions: 20,
+			autosave_secs: 60,
+			tui: !cfg!(feature = "gui"),
+			edit: None,
+			replay: false,
+			export_png: None,
+			export_gif: None,
+			export_tile: 32,
+			window_size: (1200, 800),
+			fullscreen: false,
+			fps: 10,
+		}
+	}
+}
+
+impl Options {
+	/// The options kept in the save file, those of the window.
+	pub fn settings(&self) -> Settings {
+		let (width, height) = self.window_size;
+		let mut settings = Settings::new();
+		settings.insert("window".to_owned(), format!("{width}x{height}"));
+		settings.insert("fullscreen".to_owned(), self.fullscreen.to_string());
+		settings.insert("fps".to_owned(), self.fps.to_string());
+		return settings;
+	}
+
+	/// The default options, but for the settings of a save file.
+	/// Invalid settings are ignored.
+	pub fn with_settings(settings: &Settings) -> Options {
+		let mut options = Options::default();
+		if let Some(Ok(size)) = settings.get("window").map(|s| parse_window_size(s)) {
+			options.window_size = size;
+		}
+		if let Some(Ok(fullscreen)) = settings.get("fullscreen").map(|s| s.parse()) {
+			options.fullscreen = fullscreen;
+		}
+		if let Some(Ok(fps)) = settings.get("fps").map(|s| s.parse::<u64>())
+			&& fps > 0
+		{
+			options.fps = fps;
+		}
+		return options;
+	}
+}
+
+/// Parse the arguments, without the program's name.
+/// Options taking a value accept both `--opt value` and `--opt=value`.
+pub fn parse<I: IntoIterator<Item = String>>(args: I) -> Result<Command, String> {
+	return parse_over(Options::default(), args);
+}
+
+/// Parse the arguments over `options` instead of the default ones.
+pub fn parse_over<I: IntoIterator<Item = String>>(
+	mut options: Options,
+	args: I,
+) -> Result<Command, String> {
+	let mut no_save = false;
+	let mut args = args.into_iter();
+
+	while let Some(arg) = args.next() {
+		let (name, mut inline_value) = match arg.split_once('=') {
+			Some((name, value)) if name.starts_with("--") => (name.to_owned(), Some(value.to_owned())),
+			_ => (arg, None),
+		};
+		let mut value = || {
+			inline_value
+				.take()
+				.or_else(|| args.next())
+				.ok_or_else(|| format!("Missing value for {name}."))
+		};
+
+		match name.as_str() {
+			"--map" => options.map = Some(PathBuf::from(value()?)),
+			"--save" => options.save = SaveChoice::File(PathBuf::from(value()?)),
+			"--slot" => options.save = SaveChoice::Slot(value()?),
+			"--no-save" => no_save = true,
+			"--new-game" => options.new_game = true,
+			"--no-snapshot" => options.snapshot = false,
+			"--verify-save" => options.verify_save = true,
+			"--autosave-moves" => {
+				let v = value()?;
+				options.autosave_actions =
+					v.parse().map_err(|_| format!("Invalid number of actions: {v:?}."))?;
+			}
+			"--autosave-secs" => {
+				let v = value()?;
+				options.autosave_secs = v.parse().map_err(|_| format!("Invalid number of seconds: {v:?}."))?;
+			}
+			"--tui" if !cfg!(feature = "tui") => {
+				return Err("This build has no terminal frontend, see the `tui` feature.".to_owned());
+			}
+			"--tui" => options.tui = true,
+			"--edit" if !cfg!(feature = "editor") => {
+				return Err("This build has no level editor, see the `editor` feature.".to_owned());
+			}
+			"--edit" => options.edit = Some(PathBuf::from(value()?)),
+			"--replay" if !cfg!(feature = "gui") => {
+				return Err("This build has no window to replay in, see the `gui` feature.".to_owned());
+			}
+			"--replay" => options.replay = true,
+			"--export-png" | "--export-gif" | "--export-tile" if !cfg!(feature = "export") => {
+				return Err("This build cannot export images, see the `export` feature.".to_owned());
+			}
+			"--export-png" => options.export_png = Some(PathBuf::from(value()?)),
+			"--export-gif" => options.export_gif = Some(PathBuf::from(value()?)),
+			"--export-tile" => {
+				let v = value()?;
+				options.export_tile = match v.parse::<u32>() {
+					Ok(side) if (1..=MAX_EXPORT_TILE).contains(&side) => side,
+					_ => return Err(format!("Invalid tile size: {v:?}.")),
+				};
+			}
+			"--window" => options.window_size = parse_window_size(&value()?)?,
+			"--fullscreen" => options.fullscreen = true,
+			"--fps" => {
+				let v = value()?;
+				options.fps = match v.parse::<u64>() {
+					Ok(fps) if fps > 0 => fps,
+					_ => return Err(format!("Invalid number of frames per second: {v:?}.")),
+				};
+			}
+			"--slots" => return Ok(Command::Slots(SlotCommand::List)),
+			"--copy-slot" => {
+				let (from, to) = (value()?, value()?);
+				return Ok(Command::Slots(SlotCommand::Copy { from, to }));
+			}
+			"--rename-slot" => {
+				let (from, to) = (value()?, value()?);
+				return Ok(Command::Slots(SlotCommand::Rename { from, to }));
+			}
+			"--delete-slot" => return Ok(Command::Slots(SlotCommand::Delete(value()?))),
+			"-h" | "--help" => return Ok(Command::Help),
+			"-V" | "--version" => return Ok(Command::Version),
+			_ => return Err(format!("Unknown argument: {name:?}.")),
+		}
+		if inline_value.is_some() {
+			return Err(format!("{name} does not take a value."));
+		}
+	}
+
+	if no_save {
+		options.save = SaveChoice::Nowhere;
+	}
+	return Ok(Command::Play(options));
+}
+
+/// Parse a window size such as `1200x800`.
+fn parse_window_size(s: &str) -> Result<(u32, u32), String> {
+	let error = || format!("Invalid window size: {s:?}, expected WIDTHxHEIGHT.");
+	let (w, h) = s.split_once(['x', 'X']).ok_or_else(error)?;
+	match (w.parse::<u32>(), h.parse::<u32>()) {
+		(Ok(w), Ok(h)) if w > 0 && h > 0 => Ok((w, h)),
+		_ => Err(error()),
+	}
+}
+
+/// Ask the player which slot to play in, by number or by name.
+/// A new name makes a new slot, nothing at all the default one.
+pub fn pick_slot(slots: &Slots, names: &[String]) -> io::Result<String> {
+	println!("Save slots:");
+	for (i, name) in names.iter().enumerate() {
+		println!("  {}. {name}", i + 1);
+	}
+	let mut stdin = io::stdin().lock();
+	loop {
+		print!("Slot to play in, by number or name. A new name makes a new slot. [{DEFAULT_SLOT}] ");
+		io::stdout().flush()?;
+		let mut line = String::new();
+		if stdin.read_line(&mut line)? == 0 {
+			return Ok(DEFAULT_SLOT.to_owned());
+		}
+		let answer = line.trim();
+		if answer.is_empty() {
+			return Ok(DEFAULT_SLOT.to_owned());
+		}
+		if let Ok(n) = answer.parse::<usize>()
+			&& (1..=names.len()).contains(&n)
+		{
+			return Ok(names[n - 1].clone());
+		}
+		match slots.path(answer) {
+			Ok(_) => return Ok(answer.to_owned()),
+			Err(e) => println!("{e}."),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn parse_line(line: &str) -> Result<Command, String> {
+		parse(line.split_whitespace().map(str::to_owned))
+	}
+
+	fn options(line: &str) -> Options {
+		match parse_line(line) {
+			Ok(Command::Play(options)) => options,
+			other => panic!("{line:?} gave {other:?}"),
+		}
+	}
+
+	#[test]
+	fn values_follow_their_option_or_an_equal_sign() {
+		let parsed = options("--map maps/a.txt --fps=5 --window 800x600 --save=b.txt --new-game");
+		assert_eq!(parsed.map, Some(PathBuf::from("maps/a.txt")));
+		assert_eq!(parsed.fps, 5);
+		assert_eq!(parsed.window_size, (800, 600));
+		assert_eq!(parsed.save, SaveChoice::File(PathBuf::from("b.txt")));
+		assert_eq!(options("--slot bob").save, SaveChoice::Slot("bob".to_owned()));
+		assert_eq!(options("--autosave-moves=0").autosave_actions, 0);
+		let saves = options("--no-snapshot --verify-save");
+		assert!(!saves.snapshot && saves.verify_save);
+		assert!(parsed.new_game);
+		assert_eq!(options(""), Options::default());
+	}
+
+	#[test]
+	fn frontend_depends_on_the_build() {
+		assert_eq!(Options::default().tui, !cfg!(feature = "gui"));
+		match cfg!(feature = "tui") {
+			true => assert!(options("--tui").tui),
+			false => assert!(parse_line("--tui").is_err()),
+		}
+		match cfg!(feature = "gui") {
+			true => assert!(options("--replay").replay),
+			false => assert!(parse_line("--replay").is_err()),
+		}
+		match cfg!(feature = "editor") {
+			true => assert_eq!(options("--edit m.txt").edit, Some(PathBuf::from("m.txt"))),
+			false => assert!(parse_line("--edit m.txt").is_err()),
+		}
+	}
+
+	#[test]
+	fn no_save_wins_over_the_save_options() {
+		assert_eq!(options("--no-save --save a.save").save, SaveChoice::Nowhere);
+		assert_eq!(options("--slot bob --no-save").save, SaveChoice::Nowhere);
+		assert_eq!(options("--slot bob --save a.save").save, SaveChoice::File(PathBuf::from("a.save")));
+	}
+
+	#[test]
+	fn the_window_settings_are_read_back() {
+		let mut options = options("--window 640x480 --fullscreen --fps 30");
+		options.map = Some(PathBuf::from("a.txt"));
+		let read = Options::with_settings(&options.settings());
+		assert_eq!(read.window_size, (640, 480));
+		assert!(read.fullscreen);
+		assert_eq!(read.fps, 30);
+		assert_eq!(read.map, None);
+
+		let mut settings = options.settings();
+		settings.insert("fps".to_owned(), "0".to_owned());
+		settings.insert("window".to_owned(), "big".to_owned());
+		let read = Options::with_settings(&settings);
+		assert_eq!((read.window_size, read.fps), (Options::default().window_size, Options::default().fps));
+	}
+
+	#[test]
+	fn invalid_arguments_are_refused() {
+		let errors = [
+			("--fps", "Missing value for --fps."),
+			("--fps=0", "Invalid number of frames per second: \"0\"."),
+			("--autosave-secs -1", "Invalid number of seconds: \"-1\"."),
+			("--window 800", "Invalid window size: \"800\", expected WIDTHxHEIGHT."),
+			("--fullscreen=yes", "--fullscreen does not take a value."),
+			("--map a.txt b.txt", "Unknown argument: \"b.txt\"."),
+		];
+		for (line, error) in errors {
+			assert_eq!(parse_line(line), Err(error.to_owned()), "{line}");
+		}
+	}
+
+	#[test]
+	fn help_and_version_end_the_arguments() {
+		assert_eq!(parse_line("--map a.txt -h --bogus"), Ok(Command::Help));
+		assert_eq!(parse_line("--version"), Ok(Command::Version));
+	}
+
+	#[test]
+	fn slot_commands_end_the_arguments() {
+		assert_eq!(parse_line("--slots"), Ok(Command::Slots(SlotCommand::List)));
+		let copy = SlotCommand::Copy {
+			from: "a".to_owned(),
+			to: "b".to_owned(),
+		};
+		assert_eq!(parse_line("--fps 5 --copy-slot a b --bogus"), Ok(Command::Slots(copy)));
+		assert_eq!(parse_line("--delete-slot"), Err("Missing value for --delete-slot.".to_owned()));
+	}
+}
@@ -0,0 +1,525 @@
+//!
+//! Edit a map: paint its tiles, place its snowballs and the player's start,
+//! with undo and redo, give rules to its levels, then write it in the
+//! `map.txt` format. Only what this format can tell is allowed: the snowballs
+//! lie on dirt or snow, and the player starts on an empty tile.
+//!
+
+use std::cell::OnceCell;
+
+use crate::game::level::Levels;
+use crate::game::portal::Portals;
+use crate::game::rule::{self, Rule};
+use crate::game::{Game, MapError, SnowBall, Tile};
+
+/// How many edits can be undone.
+const MAX_UNDO: usize = 1000;
+
+/// What painting a tile of the map does.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Brush {
+	Tile(Tile),
+	SnowBall(SnowBall),
+	/// Remove the snowball.
+	Erase,
+	/// Move the player's start there.
+	Player,
+	/// A portal, linked to the last one painted if it has no other end yet.
+	Portal,
+	/// Give the rule to the level of the tile.
+	Rule(Rule),
+	/// Remove the rules of the level of the tile.
+	NoRules,
+}
+
+impl Brush {
+	/// The brushes of the editor, in the order of the palette.
+	pub const ALL: [Brush; 19] = [
+		Brush::Tile(Tile::Empty),
+		Brush::Tile(Tile::Dirt),
+		Brush::Tile(Tile::Snow),
+		Brush::Tile(Tile::Hedge),
+		Brush::Tile(Tile::Tree),
+		Brush::Tile(Tile::Obstacle),
+		Brush::Tile(Tile::Ice),
+		Brush::Portal,
+		Brush::SnowBall(SnowBall::Small),
+		Brush::SnowBall(SnowBall::Medium),
+		Brush::SnowBall(SnowBall::Large),
+		Brush::SnowBall(SnowBall::SmallOnMedium),
+		Brush::SnowBall(SnowBall::SmallOnLarge),
+		Brush::SnowBall(SnowBall::MediumOnLarge),
+		Brush::Erase,
+		Brush::Player,
+		Brush::Rule(Rule::NoGrowth),
+		Brush::Rule(Rule::NoReset),
+		Brush::NoRules,
+	];
+
+	pub fn name(self) -> &'static str {
+		match self {
+			Brush::Tile(Tile::Empty) => "empty",
+			Brush::Tile(Tile::Dirt) => "dirt",
+			Brush::Tile(Tile::Snow) => "snow",
+			Brush::Tile(Tile::Hedge) => "hedge",
+			Brush::Tile(Tile::Tree) => "tree",
+			Brush::Tile(Tile::Obstacle) => "obstacle",
+			Brush::Tile(Tile::Ice) => "ice",
+			Brush::Tile(Tile::Portal(_)) | Brush::Portal => "portal",
+			Brush::SnowBall(SnowBall::Small) => "small ball",
+			Brush::SnowBall(SnowBall::Medium) => "medium ball",
+			Brush::SnowBall(SnowBall::Large) => "large ball",
+			Brush::SnowBall(SnowBall::SmallOnMedium) => "small on medium ball",
+			Brush::SnowBall(SnowBall::SmallOnLarge) => "small on large ball",
+			Brush::SnowBall(SnowBall::MediumOnLarge) => "medium on large ball",
+			Brush::SnowBall(SnowBall::Snowman) => "snowman",
+			Brush::Erase => "no ball",
+			Brush::Player => "player's start",
+			Brush::Rule(Rule::NoGrowth) => "no-growth rule",
+			Brush::Rule(Rule::NoReset) => "no-reset rule",
+			Brush::NoRules => "no rules",
+		}
+	}
+}
+
+/// Everything the map tells.
+#[derive(Clone, PartialEq, Eq, Debug)]
+struct Board {
+	tiles: Vec<Vec<Tile>>,
+	snowballs: Vec<Vec<Option<SnowBall>>>,
+	player: (usize, usize),
+	/// The rules, each given to the level of a tile. A rule is lost once its
+	/// tile is no longer part of a level.
+	rules: Vec<((usize, usize), Rule)>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Editor {
+	board: Board,
+	/// The boards before the last edits, the most recent last.
+	undo: Vec<Board>,
+	/// The boards of the undone edits, the most recent last.
+	redo: Vec<Board>,
+	/// The board before the stroke in progress, if any.
+	stroke: Option<Board>,
+	/// The levels of the board, found again after each edit.
+	levels: OnceCell<Levels>,
+}
+
+impl Editor {
+	/// An empty map, with the player in the top-left corner.
+	pub fn new(size_x: usize, size_y: usize) -> Editor {
+		let board = Board {
+			tiles: vec![vec![Tile::Empty; size_y.max(1)]; size_x.max(1)],
+			snowballs: vec![vec![None; size_y.max(1)]; size_x.max(1)],
+			player: (0, 0),
+			rules: Vec::new(),
+		};
+		return Editor::of(board);
+	}
+
+	/// Edit a map in the `map.txt` format.
+	pub fn from_map_str(map: &str) -> Result<Editor, MapError> {
+		let game = Game::from_map_str(map)?;
+		let rules = game
+			.levels()
+			.iter()
+			.flat_map(|level| level.rules.iter().map(|&rule| (level.cells[0], rule)))
+			.collect();
+		let board = Board {
+			tiles: game.tiles,
+			snowballs: game.snowballs,
+			player: game.player,
+			rules,
+		};
+		return Ok(Editor::of(board));
+	}
+
+	fn of(board: Board) -> Editor {
+		Editor {
+			board,
+			undo: Vec::new(),
+			redo: Vec::new(),
+			stroke: None,
+			levels: OnceCell::new(),
+		}
+	}
+
+	pub fn size_x(&self) -> usize {
+		self.board.tiles.len()
+	}
+
+	pub fn size_y(&self) -> usize {
+		self.board.tiles[0].len()
+	}
+
+	pub fn tile(&self, x: usize, y: usize) -> Tile {
+		self.board.tiles[x][y]
+	}
+
+	pub fn snowball(&self, x: usize, y: usize) -> Option<SnowBall> {
+		self.board.snowballs[x][y]
+	}
+
+	pub fn player(&self) -> (usize, usize) {
+		self.board.player
+	}
+
+	/// Whether the brush can be used on a tile of the map:
+	/// - a snowball must lie on dirt or snow, and cannot be a snowman,
+	/// - the player's start is an empty tile, which cannot be painted over,
+	/// - there are nine pairs of portals at most,
+	/// - the rules are given to levels.
+	pub fn allows(&self, (x, y): (usize, usize), brush: Brush) -> bool {
+		if x >= self.size_x() || y >= self.size_y() {
+			return false;
+		}
+		let on_player = self.board.player == (x, y);
+		return match brush {
+			Brush::Tile(tile) => !on_player || tile == Tile::Empty,
+			Brush::SnowBall(snowball) => {
+				!on_player
+					&& snowball != SnowBall::Snowman
+					&& matches!(self.board.tiles[x][y], Tile::Dirt | Tile::Snow)
+			}
+			Brush::Portal => !on_player && self.next_portal().is_some(),
+			Brush::Erase | Brush::Player => true,
+			Brush::Rule(_) | Brush::NoRules => self.levels().at((x, y)).is_some(),
+		};
+	}
+
+	/// The levels of the map, found once per edit.
+	fn levels(&self) -> &Levels {
+		self.levels.get_or_init(|| {
+			let game = Game::from_map_str(&self.rows_string()).expect("The edited map is valid.");
+			return game.levels().clone();
+		})
+	}
+
+	/// The tiles of the level the tile is part of.
+	fn level_cells(&self, cell: (usize, usize)) -> Vec<(usize, usize)> {
+		let level = self.levels().at(cell);
+		return level.expect("The rules are given to levels.").cells.clone();
+	}
+
+	/// The number of the next portal painted: the first one with a single end,
+	/// so that the portals are painted two by two, or else the first unused one.
+	fn next_portal(&self) -> Option<u8> {
+		let ends = Portals::ends(&self.board.tiles);
+		let number = ends.iter().position(|ends| ends.len() == 1);
+		let number = number.or_else(|| ends.iter().position(Vec::is_empty))?;
+		return Some(number as u8 + 1);
+	}
+
+	/// Paint a tile of the map with the brush, as part of the stroke in
+	/// progress. Return `false` if nothing changed, because the tile was
+	/// already so or the brush is not allowed there.
+	pub fn paint(&mut self, (x, y): (usize, usize), brush: Brush) -> bool {
+		if !self.allows((x, y), brush) {
+			return false;
+		}
+		if brush == Brush::Portal && matches!(self.board.tiles[x][y], Tile::Portal(_)) {
+			return false;
+		}
+		let portal = self.next_portal();
+		let before = self.board.clone();
+		let board = &mut self.board;
+		match brush {
+			Brush::Tile(tile) => {
+				board.tiles[x][y] = tile;
+				if !matches!(tile, Tile::Dirt | Tile::Snow) {
+					board.snowballs[x][y] = None;
+				}
+			}
+			Brush::SnowBall(snowball) => board.snowballs[x][y] = Some(snowball),
+			Brush::Erase => board.snowballs[x][y] = None,
+			Brush::Portal => {
+				board.tiles[x][y] = Tile::Portal(portal.expect("A portal is allowed."));
+				board.snowballs[x][y] = None;
+			}
+			Brush::Player => {
+				board.player = (x, y);
+				board.tiles[x][y] = Tile::Empty;
+				board.snowballs[x][y] = None;
+			}
+			Brush::Rule(rule) => {
+				let cells = self.level_cells((x, y));
+				let rules = &mut self.board.rules;
+				if !rules.iter().any(|&(cell, r)| r == rule && cells.binary_search(&cell).is_ok()) {
+					rules.push(((x, y), rule));
+				}
+			}
+			Brush::NoRules => {
+				let cells = self.level_cells((x, y));
+				self.board.rules.retain(|(cell, _)| cells.binary_search(cell).is_err());
+			}
+		}
+		if self.board == before {
+			return false;
+		}
+		self.levels.take();
+		self.stroke.get_or_insert(before);
+		return true;
+	}
+
+	/// Make the map `size_x` rows by `size_y` columns, adding or removing
+	/// them at the bottom and on the right. Return `false` if this would
+	/// remove the player's start.
+	pub fn resize(&mut self, size_x: usize, size_y: usize) -> bool {
+		let (px, py) = self.board.player;
+		if px >= size_x || py >= size_y || (size_x, size_y) == (self.size_x(), self.size_y()) {
+			return false;
+		}
+		self.end_stroke();
+		let before = self.board.clone();
+		let board = &mut self.board;
+		board.tiles.resize(size_x, vec![Tile::Empty; size_y]);
+		board.snowballs.resize(size_x, vec![None; size_y]);
+		for row in board.tiles.iter_mut() {
+			row.resize(size_y, Tile::Empty);
+		}
+		for row in board.snowballs.iter_mut() {
+			row.resize(size_y, None);
+		}
+		board.rules.retain(|&((x, y), _)| x < size_x && y < size_y);
+		self.levels.take();
+		self.push_undo(before);
+		return true;
+	}
+
+	/// End the stroke in progress, which is undone all at once.
+	pub fn end_stroke(&mut self) {
+		if let Some(before) = self.stroke.take() {
+			self.push_undo(before);
+		}
+	}
+
+	fn push_undo(&mut self, before: Board) {
+		if self.undo.len() >= MAX_UNDO {
+			self.undo.remove(0);
+		}
+		self.undo.push(before);
+		self.redo.clear();
+	}
+
+	/// Undo the last edit. Return `false` if there is none.
+	pub fn undo(&mut self) -> bool {
+		self.end_stroke();
+		let Some(before) = self.undo.pop() else {
+			return false;
+		};
+		self.redo.push(std::mem::replace(&mut self.board, before));
+		self.levels.take();
+		return true;
+	}
+
+	/// Redo the last undone edit. Return `false` if there is none.
+	pub fn redo(&mut self) -> bool {
+		self.end_stroke();
+		let Some(after) = self.redo.pop() else {
+			return false;
+		};
+		self.undo.push(std::mem::replace(&mut self.board, after));
+		self.levels.take();
+		return true;
+	}
+
+	/// The map in the `map.txt` format, one line per row, then one line per
+	/// level with rules, given at its top-left tile.
+	pub fn to_map_string(&self) -> String {
+		let mut map = self.rows_string();
+		if self.board.rules.is_empty() {
+			return map;
+		}
+		for level in self.levels().iter() {
+			let mut rules: Vec<Rule> = self
+				.board
+				.rules
+				.iter()
+				.filter(|&&(cell, _)| level.contains(cell))
+				.map(|&(_, rule)| rule)
+				.collect();
+			if rules.is_empty() {
+				continue;
+			}
+			rules.sort_unstable();
+			rules.dedup();
+			map.push_str(&rule::format_line(level.cells[0], &rules));
+			map.push('\n');
+		}
+		return map;
+	}
+
+	/// The rows of the map in the `map.txt` format, without the rules.
+	fn rows_string(&self) -> String {
+		let mut map = String::with_capacity(self.size_x() * (self.size_y() + 1));
+		for x in 0..self.size_x() {
+			for y in 0..self.size_y() {
+				map.push(match (x, y) == self.board.player {
+					true => 'P',
+					false => map_char(self.board.tiles[x][y], self.board.snowballs[x][y]),
+				});
+			}
+			map.push('\n');
+		}
+		return map;
+	}
+
+	/// A new game on the map, to try it.
+	pub fn to_game(&self) -> Game {
+		Game::from_map_str(&self.to_map_string()).expect("The edited map is valid.")
+	}
+}
+
+/// The character of a tile of `map.txt`. The snowballs are lowercase on dirt
+/// and uppercase on snow.
+fn map_char(tile: Tile, snowball: Option<SnowBall>) -> char {
+	let c = match snowball {
+		None => match tile {
+			Tile::Empty => ' ',
+			Tile::Dirt => '.',
+			Tile::Snow => ',',
+			Tile::Hedge => '#',
+			Tile::Tree => 't',
+			Tile::Obstacle => 'o',
+			Tile::Ice => 'i',
+			Tile::Portal(number) => char::from(b'0' + number),
+		},
+		Some(SnowBall::Small) => 's',
+		Some(SnowBall::Medium) => 'm',
+		Some(SnowBall::Large) => 'l',
+		Some(SnowBall::SmallOnMedium) => 'x',
+		Some(SnowBall::SmallOnLarge) => 'y',
+		Some(SnowBall::MediumOnLarge) => 'z',
+		Some(SnowBall::Snowman) => unreachable!("The editor places no snowman."),
+	};
+	return match tile {
+		Tile::Snow => c.to_ascii_uppercase(),
+		_ => c,
+	};
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	const DIRT: Brush = Brush::Tile(Tile::Dirt);
+
+	#[test]
+	fn a_stroke_is_undone_and_redone_at_once() {
+		let mut editor = Editor::new(2, 3);
+		assert!(editor.paint((1, 0), DIRT));
+		assert!(editor.paint((1, 1), DIRT));
+		assert!(!editor.paint((1, 1), DIRT));
+		editor.end_stroke();
+		assert!(!editor.paint((1, 2), Brush::SnowBall(SnowBall::Small)));
+		assert!(editor.paint((1, 1), Brush::SnowBall(SnowBall::Small)));
+		editor.end_stroke();
+		assert_eq!(editor.to_map_string(), "P  \n.s \n");
+
+		assert!(editor.undo());
+		assert_eq!(editor.to_map_string(), "P  \n.. \n");
+		assert!(editor.undo());
+		assert_eq!(editor.to_map_string(), "P  \n   \n");
+		assert!(!editor.undo());
+		assert!(editor.redo());
+		assert!(editor.redo());
+		assert!(!editor.redo());
+		assert_eq!(editor.to_map_string(), "P  \n.s \n");
+
+		// A new edit forgets the undone ones.
+		assert!(editor.undo());
+		assert!(editor.resize(2, 2));
+		assert!(!editor.redo());
+		assert_eq!(editor.to_map_string(), "P \n..\n");
+	}
+
+	#[test]
+	fn only_the_last_edits_are_undone() {
+		let mut editor = Editor::new(1, 2);
+		for i in 0..=MAX_UNDO {
+			let tile = [Tile::Dirt, Tile::Snow][i % 2];
+			assert!(editor.paint((0, 1), Brush::Tile(tile)));
+			editor.end_stroke();
+		}
+		let mut undone = 0;
+		while editor.undo() {
+			undone += 1;
+		}
+		assert_eq!(undone, MAX_UNDO);
+		assert_eq!(editor.tile(0, 1), Tile::Dirt);
+	}
+
+	#[test]
+	fn the_player_starts_on_an_empty_tile() {
+		let mut editor = Editor::from_map_str("P.\n,,\n").unwrap();
+		assert!(!editor.allows((0, 0), DIRT));
+		assert!(!editor.allows((0, 0), Brush::SnowBall(SnowBall::Small)));
+		assert!(!editor.allows((2, 0), Brush::Player));
+		assert!(editor.paint((1, 1), Brush::Player));
+		assert_eq!(editor.player(), (1, 1));
+		assert_eq!(editor.to_map_string(), " .\n,P\n");
+	}
+
+	#[test]
+	fn portals_are_painted_two_by_two() {
+		let mut editor = Editor::new(1, 5);
+		assert!(editor.paint((0, 1), Brush::Portal));
+		assert!(editor.paint((0, 2), Brush::Portal));
+		assert!(editor.paint((0, 3), Brush::Portal));
+		assert!(!editor.paint((0, 3), Brush::Portal));
+		assert_eq!(editor.to_map_string(), "P112 \n");
+		// The portal left alone is linked to the next one.
+		assert!(editor.paint((0, 1), Brush::Tile(Tile::Empty)));
+		assert!(editor.paint((0, 4), Brush::Portal));
+		assert_eq!(editor.to_map_string(), "P 121\n");
+
+		let mut editor = Editor::new(2, 10);
+		for y in 0..10 {
+			assert!(editor.paint((1, y), Brush::Portal));
+		}
+		for y in 1..9 {
+			assert!(editor.paint((0, y), Brush::Portal));
+		}
+		assert!(!editor.allows((0, 9), Brush::Portal));
+	}
+
+	#[test]
+	fn rules_are_given_to_levels() {
+		let mut editor = Editor::from_map_str("P  \n.. \n  .\n").unwrap();
+		let no_growth = Brush::Rule(Rule::NoGrowth);
+		assert!(!editor.allows((0, 1), no_growth));
+		assert!(editor.paint((1, 1), no_growth));
+		assert!(!editor.paint((1, 0), no_growth));
+		assert!(editor.paint((2, 2), Brush::Rule(Rule::NoReset)));
+		assert!(editor.paint((2, 2), no_growth));
+		editor.end_stroke();
+		assert_eq!(
+			editor.to_map_string(),
+			"P  \n.. \n  .\n!level 2:1 no-growth\n!level 3:3 no-growth no-reset\n"
+		);
+		let reloaded = Editor::from_map_str(&editor.to_map_string()).unwrap();
+		assert_eq!(reloaded.to_map_string(), editor.to_map_string());
+
+		assert!(editor.paint((2, 2), Brush::NoRules));
+		assert!(!editor.paint((2, 2), Brush::NoRules));
+		editor.end_stroke();
+		assert_eq!(editor.to_map_string(), "P  \n.. \n  .\n!level 2:1 no-growth\n");
+
+		// The rule is lost with its tile, the rest of the level has none.
+		assert!(editor.paint((1, 1), Brush::Tile(Tile::Empty)));
+		assert!(!editor.allows((1, 1), no_growth));
+		assert!(editor.allows((1, 0), no_growth));
+		assert_eq!(editor.to_map_string(), "P  \n.  \n  .\n");
+		editor.end_stroke();
+		assert!(editor.undo());
+		assert!(editor.allows((1, 1), no_growth));
+		assert_eq!(editor.to_map_string(), "P  \n.. \n  .\n!level 2:1 no-growth\n");
+	}
+
+	#[test]
+	fn map_is_written_back_as_read() {
+		let map = "P.,s\nxyzS\nXYZ#\n";
+		assert_eq!(Editor::from_map_str(map).unwrap().to_map_string(), map);
+	}
+}
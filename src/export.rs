@@ -0,0 +1,154 @@
+//!
+//! Draw the game into image files without opening a window: the board as a
+//! PNG, or the replay of a history as an animated GIF. The board is the one
+//! of the window, drawn by `view` on a canvas in memory.
+//!
+
+mod canvas;
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufWriter};
+use std::path::Path;
+
+use graphics::{Context, Transformed};
+
+use snowcrab::game::Game;
+use snowcrab::replay::Replay;
+
+use crate::view::{self, Viewport, TILE_SIDE};
+use canvas::Canvas;
+
+/// How long each state of a replay is shown, in hundredths of a second.
+const FRAME_DELAY: u16 = 10;
+/// How long the last state is shown before the animation starts over.
+const LAST_FRAME_DELAY: u16 = 200;
+/// The quality of the palette of a frame of more than 256 colors, from 1 the
+/// best to 30 the fastest.
+const QUANTIZATION_SPEED: i32 = 10;
+
+/// Write the whole board as a PNG, with tiles `tile_side` pixels wide.
+pub fn write_png(game: &Game, tile_side: u32, path: &Path) -> io::Result<()> {
+	let canvas = draw_board(game, tile_side);
+	let file = BufWriter::new(File::create(path)?);
+	let mut encoder = png::Encoder::new(file, canvas.width(), canvas.height());
+	encoder.set_color(png::ColorType::Rgba);
+	encoder.set_depth(png::BitDepth::Eight);
+	let mut writer = encoder.write_header()?;
+	writer.write_image_data(canvas.pixels())?;
+	writer.finish()?;
+	return Ok(());
+}
+
+/// Write the replay from its start to its end as a looping GIF of the whole
+/// board, one frame per action. Each frame holds only what the action changed.
+pub fn write_gif(replay: &mut Replay, tile_side: u32, path: &Path) -> io::Result<()> {
+	replay.seek(0);
+	let mut previous = draw_board(replay.game(), tile_side);
+	let (Ok(width), Ok(height)) = (u16::try_from(previous.width()), u16::try_from(previous.height())) else {
+		return Err(io::Error::new(
+			io::ErrorKind::InvalidInput,
+			"the board is too large for a GIF, use smaller tiles",
+		));
+	};
+	let file = BufWriter::new(File::create(path)?);
+	let mut encoder = gif::Encoder::new(file, width, height, &[]).map_err(io::Error::other)?;
+	encoder.set_repeat(gif::Repeat::Infinite).map_err(io::Error::other)?;
+
+	let mut frame = gif_frame(&previous, (0, 0, previous.width(), previous.height()));
+	while replay.step_forward() {
+		frame.delay = FRAME_DELAY;
+		encoder.write_frame(&frame).map_err(io::Error::other)?;
+		let canvas = draw_board(replay.game(), tile_side);
+		// A frame cannot be empty, one pixel is redrawn if nothing changed.
+		let area = canvas.changed_area(&previous).unwrap_or((0, 0, 1, 1));
+		frame = gif_frame(&canvas, area);
+		previous = canvas;
+	}
+	frame.delay = LAST_FRAME_DELAY;
+	encoder.write_frame(&frame).map_err(io::Error::other)?;
+	return Ok(());
+}
+
+/// The whole board in a canvas, with tiles `tile_side` pixels wide.
+fn draw_board(game: &Game, tile_side: u32) -> Canvas {
+	let mut canvas = Canvas::new(game.size_y() as u32 * tile_side, game.size_x() as u32 * tile_side);
+	let scale = tile_side as f64 / TILE_SIDE;
+	let context = Context::new_abs(canvas.width() as f64, canvas.height() as f64).scale(scale, scale);
+	let vp = Viewport {
+		base_x: 0,
+		base_y: 0,
+		len_x: game.size_x(),
+		len_y: game.size_y(),
+	};
+	view::draw_all(vp, game, context, &mut canvas);
+	return canvas;
+}
+
+/// The frame of the rectangle `(left, top, width, height)` of the canvas.
+/// The board being drawn without anti-aliasing, its few colors make an exact
+/// palette. A palette is computed in case there are too many of them.
+fn gif_frame(canvas: &Canvas, area: (u32, u32, u32, u32)) -> gif::Frame<'static> {
+	let (left, top, width, height) = area;
+	let mut bytes = canvas.area(area);
+	let mut palette = Vec::new();
+	let mut indices = HashMap::new();
+	let mut pixels = Vec::with_capacity(bytes.len() / 4);
+	for pixel in bytes.chunks_exact(4) {
+		let next = indices.len();
+		let index = *indices.entry([pixel[0], pixel[1], pixel[2]]).or_insert_with(|| {
+			palette.extend_from_slice(&pixel[..3]);
+			next
+		});
+		pixels.push(index as u8);
+		if indices.len() > 256 {
+			break;
+		}
+	}
+	let mut frame = if indices.len() > 256 {
+		gif::Frame::from_rgba_speed(width as u16, height as u16, &mut bytes, QUANTIZATION_SPEED)
+	} else {
+		gif::Frame::from_palette_pixels(width as u16, height as u16, pixels, palette, None)
+	};
+	frame.left = left as u16;
+	frame.top = top as u16;
+	return frame;
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::palette;
+	use snowcrab::game::Tile;
+
+	#[test]
+	fn a_tile_is_drawn_in_its_color() {
+		let game = Game::from_map_str("P .\n").unwrap();
+		let side = 16;
+		let canvas = draw_board(&game, side);
+		assert_eq!((canvas.width(), canvas.height()), (3 * side, side));
+		// The center of the dirt tile, on the third column.
+		let center = canvas.area((2 * side + side / 2, side / 2, 1, 1));
+		let dirt = palette::tile_color(Tile::Dirt).map(|value| (value * 255.0).round() as u8);
+		assert_eq!(center, dirt);
+	}
+
+	#[test]
+	fn a_replay_has_a_frame_per_state() {
+		let path = std::env::temp_dir().join(format!("snowcrab-export-{}.gif", std::process::id()));
+		let game = Game::from_map_str("P  \n.s.\n").unwrap();
+		// The last move is blocked, the replay ends before it.
+		let mut replay = Replay::new(game, "RRR").unwrap();
+		write_gif(&mut replay, 8, &path).unwrap();
+
+		let file = File::open(&path).unwrap();
+		let mut decoder = gif::DecodeOptions::new().read_info(file).unwrap();
+		assert_eq!((decoder.width(), decoder.height()), (24, 16));
+		let mut delays = Vec::new();
+		while let Some(frame) = decoder.read_next_frame().unwrap() {
+			delays.push(frame.delay);
+		}
+		assert_eq!(delays, [FRAME_DELAY, FRAME_DELAY, LAST_FRAME_DELAY]);
+		std::fs::remove_file(path).unwrap();
+	}
+}
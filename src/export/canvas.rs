@@ -0,0 +1,246 @@
+//!
+//! A backend of piston's graphics drawing in an RGBA buffer in memory, on the
+//! CPU. The triangles are filled without anti-aliasing, and the textures are
+//! ignored since the board has none.
+//!
+
+use graphics::draw_state::DrawState;
+use graphics::types::Color;
+use graphics::{Graphics, ImageSize};
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Canvas {
+	width: u32,
+	height: u32,
+	/// The RGBA bytes of each pixel, row by row from the top.
+	pixels: Vec<u8>,
+}
+
+/// The texture type of the canvas, which draws none.
+pub struct NoTexture;
+
+impl ImageSize for NoTexture {
+	fn get_size(&self) -> (u32, u32) {
+		(0, 0)
+	}
+}
+
+impl Canvas {
+	/// A transparent canvas.
+	pub fn new(width: u32, height: u32) -> Canvas {
+		Canvas {
+			width,
+			height,
+			pixels: vec![0; width as usize * height as usize * 4],
+		}
+	}
+
+	pub fn width(&self) -> u32 {
+		self.width
+	}
+
+	pub fn height(&self) -> u32 {
+		self.height
+	}
+
+	pub fn pixels(&self) -> &[u8] {
+		&self.pixels
+	}
+
+	/// The RGBA bytes of the rectangle `(left, top, width, height)`.
+	pub fn area(&self, (left, top, width, height): (u32, u32, u32, u32)) -> Vec<u8> {
+		let mut bytes = Vec::with_capacity(width as usize * height as usize * 4);
+		for y in top..top + height {
+			let start = (y as usize * self.width as usize + left as usize) * 4;
+			bytes.extend_from_slice(&self.pixels[start..start + width as usize * 4]);
+		}
+		return bytes;
+	}
+
+	/// The smallest rectangle `(left, top, width, height)` out of which this
+	/// canvas is the same as `other`, of the same size. `None` if they are
+	/// the same everywhere.
+	pub fn changed_area(&self, other: &Canvas) -> Option<(u32, u32, u32, u32)> {
+		let (mut left, mut top, mut right, mut bottom) = (u32::MAX, u32::MAX, 0, 0);
+		let rows = self.pixels.chunks_exact(self.width as usize * 4);
+		let other_rows = other.pixels.chunks_exact(other.width as usize * 4);
+		for (y, (row, other_row)) in rows.zip(other_rows).enumerate() {
+			if row == other_row {
+				continue;
+			}
+			let pixels = row.chunks_exact(4).zip(other_row.chunks_exact(4));
+			for (x, (pixel, other_pixel)) in pixels.enumerate() {
+				if pixel != other_pixel {
+					left = left.min(x as u32);
+					right = right.max(x as u32);
+					top = top.min(y as u32);
+					bottom = bottom.max(y as u32);
+				}
+			}
+		}
+		if left == u32::MAX {
+			return None;
+		}
+		return Some((left, top, right - left + 1, bottom - top + 1));
+	}
+
+	/// Fill the pixels whose center is in the triangle, of the colors of its
+	/// vertices blended over the canvas. The vertices are in normalized device
+	/// coordinates, from -1 to 1 with the y axis up.
+	fn fill_triangle(&mut self, vertices: &[[f32; 2]], colors: [Color; 3]) {
+		let (width, height) = (self.width as f32, self.height as f32);
+		let corners = [0, 1, 2].map(|i| {
+			let [x, y] = vertices[i];
+			[(x + 1.0) / 2.0 * width, (1.0 - y) / 2.0 * height]
+		});
+		let [a, b, c] = corners;
+		let area = edge(a, b, c);
+		if area == 0.0 {
+			return;
+		}
+		let uniform = colors[0] == colors[1] && colors[1] == colors[2];
+		let opaque = (uniform && colors[0][3] >= 1.0).then(|| to_bytes(colors[0]));
+		let top = a[1].min(b[1]).min(c[1]);
+		let bottom = a[1].max(b[1]).max(c[1]);
+		let first_row = (top - 0.5).ceil().max(0.0) as u32;
+		let last_row = ((bottom - 0.5).ceil().max(0.0) as u32).min(self.height);
+		for y in first_row..last_row {
+			let center_y = y as f32 + 0.5;
+			// Where the row crosses the edges, each edge including its top
+			// end only so that the triangles sharing it do not overlap.
+			let mut crossings = [f32::INFINITY, f32::NEG_INFINITY];
+			for (from, to) in [(a, b), (b, c), (c, a)] {
+				let (upper, lower) = if from[1] < to[1] { (from, to) } else { (to, from) };
+				if upper[1] <= center_y && center_y < lower[1] {
+					let x = upper[0] + (center_y - upper[1]) / (lower[1] - upper[1]) * (lower[0] - upper[0]);
+					crossings = [crossings[0].min(x), crossings[1].max(x)];
+				}
+			}
+			let first_column = (crossings[0] - 0.5).ceil().max(0.0) as u32;
+			let last_column = ((crossings[1] - 0.5).ceil().max(0.0) as u32).min(self.width);
+			if first_column >= last_column {
+				continue;
+			}
+			let row = y as usize * self.width as usize;
+			let span = &mut self.pixels[(row + first_column as usize) * 4..(row + last_column as usize) * 4];
+			if let Some(bytes) = opaque {
+				// Most of the board, which needs no blending.
+				for pixel in span.chunks_exact_mut(4) {
+					pixel.copy_from_slice(&bytes);
+				}
+				continue;
+			}
+			for (x, pixel) in (first_column..).zip(span.chunks_exact_mut(4)) {
+				let color = if uniform {
+					colors[0]
+				} else {
+					// The barycentric coordinates of the center of the pixel.
+					let center = [x as f32 + 0.5, center_y];
+					let weights = [edge(b, c, center), edge(c, a, center), edge(a, b, center)].map(|w| w / area);
+					let mut color = [0.0; 4];
+					for (weight, vertex_color) in weights.iter().zip(&colors) {
+						for (channel, value) in color.iter_mut().zip(vertex_color) {
+							*channel += weight * value;
+						}
+					}
+					color
+				};
+				blend(pixel, color);
+			}
+		}
+	}
+}
+
+/// Twice the signed area of the triangle `abc`.
+fn edge(a: [f32; 2], b: [f32; 2], c: [f32; 2]) -> f32 {
+	(b[0] - a[0]) * (c[1] - a[1]) - (b[1] - a[1]) * (c[0] - a[0])
+}
+
+fn to_bytes(color: Color) -> [u8; 4] {
+	color.map(|value| (value.clamp(0.0, 1.0) * 255.0).round() as u8)
+}
+
+/// Draw `color` over the RGBA bytes of a pixel.
+fn blend(pixel: &mut [u8], color: Color) {
+	let alpha = color[3].clamp(0.0, 1.0);
+	let below = pixel[3] as f32 / 255.0;
+	for (byte, value) in pixel.iter_mut().zip(&color[..3]) {
+		let mixed = value.clamp(0.0, 1.0) * alpha + *byte as f32 / 255.0 * (1.0 - alpha);
+		*byte = (mixed * 255.0).round() as u8;
+	}
+	pixel[3] = ((alpha + below * (1.0 - alpha)) * 255.0).round() as u8;
+}
+
+impl Graphics for Canvas {
+	type Texture = NoTexture;
+
+	fn clear_color(&mut self, color: Color) {
+		let bytes = to_bytes(color);
+		for pixel in self.pixels.chunks_exact_mut(4) {
+			pixel.copy_from_slice(&bytes);
+		}
+	}
+
+	fn clear_stencil(&mut self, _value: u8) {}
+
+	fn tri_list<F>(&mut self, _draw_state: &DrawState, color: &[f32; 4], mut f: F)
+	where
+		F: FnMut(&mut dyn FnMut(&[[f32; 2]])),
+	{
+		f(&mut |vertices| {
+			for triangle in vertices.chunks_exact(3) {
+				self.fill_triangle(triangle, [*color; 3]);
+			}
+		});
+	}
+
+	fn tri_list_c<F>(&mut self, _draw_state: &DrawState, mut f: F)
+	where
+		F: FnMut(&mut dyn FnMut(&[[f32; 2]], &[[f32; 4]])),
+	{
+		f(&mut |vertices, colors| {
+			for (triangle, colors) in vertices.chunks_exact(3).zip(colors.chunks_exact(3)) {
+				self.fill_triangle(triangle, [colors[0], colors[1], colors[2]]);
+			}
+		});
+	}
+
+	fn tri_list_uv<F>(&mut self, _draw_state: &DrawState, _color: &[f32; 4], _texture: &NoTexture, _f: F)
+	where
+		F: FnMut(&mut dyn FnMut(&[[f32; 2]], &[[f32; 2]])),
+	{
+	}
+
+	fn tri_list_uv_c<F>(&mut self, _draw_state: &DrawState, _texture: &NoTexture, _f: F)
+	where
+		F: FnMut(&mut dyn FnMut(&[[f32; 2]], &[[f32; 2]], &[[f32; 4]])),
+	{
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn the_changed_area_holds_every_changed_pixel() {
+		let canvas = Canvas::new(4, 3);
+		let mut other = canvas.clone();
+		assert_eq!(canvas.changed_area(&other), None);
+		other.pixels[4..8].copy_from_slice(&[255; 4]);
+		other.pixels[(2 * 4 + 2) * 4] = 1;
+		assert_eq!(canvas.changed_area(&other), Some((1, 0, 2, 3)));
+		assert_eq!(other.area((1, 0, 1, 1)), [255; 4]);
+	}
+
+	#[test]
+	fn colors_are_blended_over_the_canvas() {
+		let mut canvas = Canvas::new(2, 2);
+		canvas.clear_color([1.0, 0.0, 0.0, 1.0]);
+		assert_eq!(canvas.area((1, 1, 1, 1)), [255, 0, 0, 255]);
+		// A triangle over the left half, then the bottom-right pixel.
+		canvas.fill_triangle(&[[-1.0, 1.0], [0.0, 1.0], [-1.0, -3.0]], [[0.0, 0.0, 1.0, 0.5]; 3]);
+		assert_eq!(canvas.area((0, 0, 1, 2)), [128, 0, 128, 255, 128, 0, 128, 255]);
+		assert_eq!(canvas.area((1, 0, 1, 1)), [255, 0, 0, 255]);
+	}
+}
@@ -1,13 +1,34 @@
 //!
-//!
+//! The game's state and rules: the map, the snow balls, the player and their moves.
 //!
 
+pub mod deadlock;
+pub mod event;
+#[cfg(feature = "solver")]
+pub mod hint;
+mod history;
+pub mod level;
+pub mod portal;
+pub mod progress;
 mod reset;
+pub mod rule;
+pub mod snapshot;
+#[cfg(feature = "solver")]
+pub mod solver;
+pub mod tree;
+#[cfg(test)]
+mod tests;
 
 use std::collections::VecDeque;
+use std::path::Path;
 
-pub const SIZE_X: usize = str_to_usize(env!("SNOWCRAB_SIZE_X"));
-pub const SIZE_Y: usize = str_to_usize(env!("SNOWCRAB_SIZE_Y"));
+pub use event::{BlockReason, GameEvent};
+use history::{Checkpoint, Undo};
+use level::Levels;
+use portal::Portals;
+use progress::Progress;
+use rule::Rule;
+use tree::HistoryTree;
 
 /*
    +-----> y
@@ -26,6 +47,23 @@ pub enum SnowBall {
 }
 
 impl SnowBall {
+	/// Whether it's two balls on top of each other.
+	pub fn is_stacked(self) -> bool {
+		matches!(
+			self,
+			SnowBall::SmallOnMedium | SnowBall::SmallOnLarge | SnowBall::MediumOnLarge
+		)
+	}
+
+	/// The number of single balls it's made of.
+	pub fn ball_count(self) -> usize {
+		match self {
+			SnowBall::Small | SnowBall::Medium | SnowBall::Large => 1,
+			SnowBall::SmallOnMedium | SnowBall::SmallOnLarge | SnowBall::MediumOnLarge => 2,
+			SnowBall::Snowman => 3,
+		}
+	}
+
 	/// Return a grown version of the snowball which must be a single small, medium or large ball.
 	/// Return `None` if not applicable.
 	pub fn grow(self) -> Option<Self> {
@@ -46,6 +84,12 @@ pub enum Tile {
 	Hedge,
 	Tree,
 	Obstacle,
+
+	/// Snowballs slide on it, and do not grow.
+	Ice,
+	/// The player walking onto it comes out of the other portal of the same
+	/// number, from 1 to 9. A portal without its other end is just a floor.
+	Portal(u8),
 }
 
 #[derive(Clone, Copy, PartialEq, Eq, Debug)]
@@ -58,64 +102,133 @@ pub enum Direction {
 
 #[derive(Clone, Debug)]
 pub struct Game {
-	pub tiles: [[Tile; SIZE_Y]; SIZE_X],
-	pub snowballs: [[Option<SnowBall>; SIZE_Y]; SIZE_X],
+	pub tiles: Vec<Vec<Tile>>,
+	pub snowballs: Vec<Vec<Option<SnowBall>>>,
 	pub player: (usize, usize),
-	rewind_queue: VecDeque<Update>,
+	/// The map as it was loaded, used to reset the levels.
+	initial_tiles: Vec<Vec<Tile>>,
+	initial_snowballs: Vec<Vec<Option<SnowBall>>>,
+	initial_player: (usize, usize),
+	/// The levels of the map, found when it's loaded.
+	levels: Levels,
+	/// The pairs of linked portals, found when the map is loaded.
+	portals: Portals,
+	/// How to revert the most recent actions.
+	rewind_queue: VecDeque<Undo>,
+	/// The board every `history::CHECKPOINT_INTERVAL` actions.
+	checkpoints: Vec<Checkpoint>,
 	input_history: String,
+	/// Every line of play, the current one being `input_history`.
+	tree: HistoryTree,
+	/// The tiles where the player entered a level for the first time, and
+	/// the number of actions in the history before that.
+	level_entries: Vec<((usize, usize), usize)>,
+	/// The attempts at the levels, in the order they started.
+	attempts: Vec<progress::Attempt>,
+	progress: Progress,
+	/// The events not taken by the frontend yet.
+	events: Vec<GameEvent>,
 }
 
 impl Game {
 	pub fn process_player_input(&mut self, dir: Direction) -> bool {
-		self.process_player_input_no_history(dir)
-			.then(|| self.input_history.push(dir.into()))
-			.is_some()
+		self.perform_new(dir.into())
 	}
 
-	fn process_player_input_no_history(&mut self, dir: Direction) -> bool {
-		if let Some(map_diff) = self.step(dir) {
-			// We ain't gonna let the stack grow to out of memory and beyond.
-			if self.rewind_queue.len() >= 2048 {
-				self.rewind_queue.pop_front();
-			}
+	pub fn reset_current_level(&mut self) -> bool {
+		self.perform_new('T')
+	}
 
-			self.apply_update(&map_diff.new);
-			self.rewind_queue.push_back(map_diff.old);
-			return true;
-		} else {
+	/// Perform an action chosen by the player, following or creating the
+	/// matching branch of the history tree.
+	fn perform_new(&mut self, action: char) -> bool {
+		let level = self.level_to_complete(action);
+		if !self.perform(action) {
 			return false;
 		}
+		self.advance_in_tree(action);
+		self.check_completion(level);
+		return true;
 	}
 
-	pub fn rewind(&mut self) -> bool {
-		if let Some(update) = self.rewind_queue.pop_back() {
-			self.apply_update(&update);
-			self.input_history.pop();
-			return true;
-		} else {
-			println!("Cannot rewind any further.");
-			return false;
+	/// Perform one action of the input history's alphabet, and record it.
+	fn perform(&mut self, action: char) -> bool {
+		let undo = match Direction::try_from(action) {
+			Ok(dir) => self.process_player_input_no_history(dir),
+			Err('T') => self.reset_current_level_no_history(),
+			Err(_) => None,
+		};
+		match undo {
+			Some(undo) => {
+				self.record(action, undo);
+				return true;
+			}
+			None => return false,
 		}
 	}
 
-	pub fn reset_current_level(&mut self) -> bool {
-		self.reset_current_level_no_history()
-			.then(|| self.input_history.push('T'))
-			.is_some()
+	fn process_player_input_no_history(&mut self, dir: Direction) -> Option<Undo> {
+		let from = self.player;
+		let Some(map_diff) = self.step(dir) else {
+			let reason = self.block_reason(dir);
+			self.events.push(GameEvent::MoveBlocked { dir, reason });
+			return None;
+		};
+		self.apply_update(&map_diff.new);
+		self.note_level_entry(from);
+		if let Some(portal) = self.try_step(from.0, from.1, dir)
+			&& portal != self.player
+			&& map_diff.new.tiles.is_none()
+		{
+			self.events.push(GameEvent::Teleported {
+				from: portal,
+				to: self.player,
+			});
+		}
+		if let Some((ref tile0, ref tile1)) = map_diff.new.tiles {
+			for u in [tile0, tile1] {
+				if u.new_snowball == Some(Some(SnowBall::Snowman)) {
+					self.events.push(GameEvent::SnowmanBuilt { x: u.x, y: u.y });
+				}
+			}
+		}
+		return Some(Undo::Step(map_diff.old));
 	}
 
-	fn reset_current_level_no_history(&mut self) -> bool {
-		// TODO: How to reset the player's position?
+	fn reset_current_level_no_history(&mut self) -> Option<Undo> {
+		if self.follows(self.player, Rule::NoReset) {
+			self.events.push(GameEvent::ResetRejected);
+			return None;
+		}
 		let changes = self.current_level_diff();
-		if changes.is_empty() {
-			println!("Cannot reset the level.");
-			return false;
+		let Some(start) = self.start_after_reset() else {
+			self.events.push(GameEvent::ResetRejected);
+			return None;
+		};
+		if changes.is_empty() && start == self.player {
+			self.events.push(GameEvent::ResetRejected);
+			return None;
 		}
+		let previous_tiles = changes
+			.iter()
+			.map(|u| OneTileUpdate {
+				x: u.x,
+				y: u.y,
+				new_tile: u.new_tile.map(|_| self.tiles[u.x][u.y]),
+				new_snowball: u.new_snowball.map(|_| self.snowballs[u.x][u.y]),
+			})
+			.collect();
+		let undo = Undo::Reset {
+			player: self.player,
+			tiles: previous_tiles,
+		};
 		for update in changes.into_iter() {
 			self.apply_unit_update(&update);
 		}
-		self.rewind_queue.clear();
-		return true;
+		self.player = start;
+		let level = self.current_level().expect("The player is put back in the level.").id;
+		self.restart_attempt(level, self.input_history.len());
+		return Some(undo);
 	}
 
 	fn apply_update(&mut self, update: &Update) {
@@ -139,18 +252,14 @@ impl Game {
 	// The logic of the game goes here, basically.
 	fn step(&self, dir: Direction) -> Option<MapDiff> {
 		let (x0, y0) = self.player;
-		let target = try_step(x0, y0, dir);
-		if target.is_none() {
-			println!("Cannot move outside the map.");
-			return None;
-		}
-		let (x, y) = target.unwrap();
+		let (x, y) = self.try_step(x0, y0, dir)?;
 
 		match (self.tiles[x][y].blocks(), self.snowballs[x][y]) {
 			(true, _) | (false, Some(SnowBall::Snowman)) => {
 				return None;
 			}
 			(false, None) => {
+				let (x, y) = self.portals.exit((x, y)).unwrap_or((x, y));
 				return Some(self.player_moves(x, y));
 			}
 			(false, Some(_)) => {
@@ -165,10 +274,9 @@ impl Game {
 		target_x: usize,
 		target_y: usize,
 	) -> Option<MapDiff> {
-		if let Some((beyond_x, beyond_y)) = try_step(target_x, target_y, dir) {
-			if self.tiles[beyond_x][beyond_y].blocks()
+		if let Some((beyond_x, beyond_y)) = self.try_step(target_x, target_y, dir) {
+			if !self.tiles[beyond_x][beyond_y].holds_snowballs()
 				|| self.snowballs[beyond_x][beyond_y] == Some(SnowBall::Snowman)
-				|| self.tiles[beyond_x][beyond_y] == Tile::Empty
 			{
 				return None;
 			}
@@ -196,7 +304,9 @@ impl Game {
 					None => return None,
 				};
 			} else {
-				let beyond_is_snow = self.tiles[beyond_x][beyond_y] == Tile::Snow;
+				let (beyond_x, beyond_y) = self.slide(beyond_x, beyond_y, dir);
+				let beyond_is_snow = self.tiles[beyond_x][beyond_y] == Tile::Snow
+					&& !self.follows((beyond_x, beyond_y), Rule::NoGrowth);
 
 				match target_snowball {
 					SnowBall::Small | SnowBall::Medium | SnowBall::Large => {
@@ -248,6 +358,19 @@ impl Game {
 		}
 	}
 
+	/// Where a snowball pushed onto `(x, y)` stops: there, unless it's ice and
+	/// the ball slides on until the next tile is not free ice.
+	fn slide(&self, mut x: usize, mut y: usize, dir: Direction) -> (usize, usize) {
+		while self.tiles[x][y] == Tile::Ice
+			&& let Some((next_x, next_y)) = self.try_step(x, y, dir)
+			&& self.tiles[next_x][next_y] == Tile::Ice
+			&& self.snowballs[next_x][next_y].is_none()
+		{
+			(x, y) = (next_x, next_y);
+		}
+		return (x, y);
+	}
+
 	fn player_moves(&self, new_x: usize, new_y: usize) -> MapDiff {
 		MapDiff {
 			new: Update {
@@ -307,6 +430,7 @@ impl Game {
 		};
 	}
 
+	#[allow(clippy::too_many_arguments)]
 	fn snowball_descends(
 		&self,
 		still_snowball_x: usize,
@@ -399,6 +523,46 @@ impl Game {
 		};
 	}
 
+	/// Height of the map, the number of rows.
+	pub fn size_x(&self) -> usize {
+		self.tiles.len()
+	}
+
+	/// Width of the map, the number of columns.
+	pub fn size_y(&self) -> usize {
+		self.tiles.first().map_or(0, Vec::len)
+	}
+
+	/// A hash of the map as it was loaded, to tell which map a save was made on.
+	/// It's a FNV-1a of the initial board, the same on every build and platform.
+	pub fn map_hash(&self) -> u64 {
+		let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+		let mut write = |byte: u8| hash = (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3);
+		let (px, py) = self.initial_player;
+		for n in [self.size_x(), self.size_y(), px, py] {
+			(n as u32).to_le_bytes().into_iter().for_each(&mut write);
+		}
+		for (tiles, snowballs) in self.initial_tiles.iter().zip(&self.initial_snowballs) {
+			for (&tile, &snowball) in tiles.iter().zip(snowballs) {
+				write(tile.code());
+				write(snowball.map_or(0, |ball| ball as u8 + 1));
+			}
+		}
+		self.rule_codes().into_iter().for_each(write);
+		return hash;
+	}
+
+	/// The coordinates of the neighbour of `(x, y)` in the direction `dir`,
+	/// or `None` if it's outside the map.
+	pub fn try_step(&self, x: usize, y: usize, dir: Direction) -> Option<(usize, usize)> {
+		match dir {
+			Direction::Up => (x != 0).then(|| (x - 1, y)),
+			Direction::Right => (y + 1 < self.size_y()).then(|| (x, y + 1)),
+			Direction::Down => (x + 1 < self.size_x()).then(|| (x + 1, y)),
+			Direction::Left => (y != 0).then(|| (x, y - 1)),
+		}
+	}
+
 	pub fn get_history(&self) -> &str {
 		&self.input_history
 	}
@@ -411,23 +575,16 @@ impl Game {
 			return Err(c);
 		}
 
+		// Replaying the history is not news for the player.
+		let events = self.events.len();
 		for (i, c) in history.chars().filter(|&c| c != '\n').enumerate() {
-			let action_worked = match c {
-				'U' => self.process_player_input_no_history(Direction::Up),
-				'L' => self.process_player_input_no_history(Direction::Left),
-				'D' => self.process_player_input_no_history(Direction::Down),
-				'R' => self.process_player_input_no_history(Direction::Right),
-				'T' => self.reset_current_level_no_history(),
-				_ => unreachable!(),
-			};
-			if !action_worked {
-				println!("Warning: the save data is not coherent with the current map. Error at character of index {i}.");
-				self.input_history.push_str(&history[..i]);
+			if !self.perform_new(c) {
+				self.events.truncate(events);
+				self.events.push(GameEvent::SaveDesync { index: i });
 				return Ok(());
 			}
 		}
-
-		self.input_history.push_str(history);
+		self.events.truncate(events);
 		Ok(())
 	}
 }
@@ -436,14 +593,39 @@ impl Tile {
 	pub fn blocks(self) -> bool {
 		self == Self::Hedge || self == Self::Tree || self == Self::Obstacle
 	}
+
+	/// Whether snowballs can lie on it, which makes it part of a level.
+	pub fn holds_snowballs(self) -> bool {
+		matches!(self, Tile::Dirt | Tile::Snow | Tile::Ice)
+	}
+
+	/// A number for each tile, for `Game::map_hash`. The first tiles keep their
+	/// discriminant, so that the hash of the maps without the others is unchanged.
+	fn code(self) -> u8 {
+		match self {
+			Tile::Empty => 0,
+			Tile::Dirt => 1,
+			Tile::Snow => 2,
+			Tile::Hedge => 3,
+			Tile::Tree => 4,
+			Tile::Obstacle => 5,
+			Tile::Ice => 6,
+			Tile::Portal(number) => 6 + number,
+		}
+	}
 }
 
-fn try_step(x: usize, y: usize, dir: Direction) -> Option<(usize, usize)> {
-	match dir {
-		Direction::Up => (x != 0).then(|| (x - 1, y)),
-		Direction::Right => (y != SIZE_Y - 1).then(|| (x, y + 1)),
-		Direction::Down => (x != SIZE_X - 1).then(|| (x + 1, y)),
-		Direction::Left => (y != 0).then(|| (x, y - 1)),
+impl TryFrom<char> for Direction {
+	type Error = char;
+
+	fn try_from(c: char) -> Result<Self, Self::Error> {
+		match c {
+			'U' => Ok(Direction::Up),
+			'L' => Ok(Direction::Left),
+			'D' => Ok(Direction::Down),
+			'R' => Ok(Direction::Right),
+			_ => Err(c),
+		}
 	}
 }
 
@@ -490,34 +672,123 @@ struct MapDiff {
 
 //////////////////////////////////
 
-const MAP: &str = include_str!("../map.txt");
+/// The map of the original game, embedded in the binary.
+pub const DEFAULT_MAP: &str = include_str!("../map.txt");
+
+/// Why a map could not be loaded.
+#[derive(Debug)]
+pub enum MapError {
+	Io(std::io::Error),
+	/// The map contains no tile at all.
+	Empty,
+	/// There is no `P` character on the map.
+	MissingPlayer,
+	/// Two `P` characters were found, at these (line, column), starting at 1.
+	DuplicatePlayer {
+		first: (usize, usize),
+		second: (usize, usize),
+	},
+	/// A rule line at this line, starting at 1, cannot be applied.
+	InvalidRule { line: usize, message: String },
+}
+
+impl std::fmt::Display for MapError {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			MapError::Io(e) => write!(f, "cannot read the map: {e}"),
+			MapError::Empty => write!(f, "the map is empty"),
+			MapError::MissingPlayer => write!(f, "missing player's initial position on the map"),
+			MapError::DuplicatePlayer { first, second } => write!(
+				f,
+				"there cannot be two player's initial positions on the map, found at {}:{} and {}:{}",
+				first.0, first.1, second.0, second.1
+			),
+			MapError::InvalidRule { line, message } => write!(f, "invalid rule at line {line}: {message}"),
+		}
+	}
+}
+
+impl std::error::Error for MapError {
+	fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+		match self {
+			MapError::Io(e) => Some(e),
+			_ => None,
+		}
+	}
+}
+
+impl From<std::io::Error> for MapError {
+	fn from(e: std::io::Error) -> Self {
+		MapError::Io(e)
+	}
+}
 
 impl Game {
+	/// A new game on the embedded map.
 	pub fn instanciate() -> Game {
-		let mut tiles = [[Tile::Empty; SIZE_Y]; SIZE_X];
-		let mut snowballs = [[None; SIZE_Y]; SIZE_X];
-		let mut player_pos = None::<(usize, usize)>;
+		Game::from_map_str(DEFAULT_MAP).expect("The embedded map must be valid.")
+	}
 
-		for (i, c) in MAP.chars().filter(|c| *c != '\n').enumerate() {
-			let x = i / SIZE_Y;
-			let y = i % SIZE_Y;
+	pub fn from_map_file<P: AsRef<Path>>(path: P) -> Result<Game, MapError> {
+		let map = std::fs::read_to_string(path)?;
+		return Game::from_map_str(&map);
+	}
+
+	/// Parse a map in the `map.txt` format: one line per row of tiles, then
+	/// the rule lines of some levels, see `rule`.
+	/// Rows shorter than the longest one are padded with empty tiles.
+	pub fn from_map_str(map: &str) -> Result<Game, MapError> {
+		let (rows, rule_lines) = rule::split_map(map);
+		let size_x = rows.len();
+		let size_y = rows.iter().map(|row| row.chars().count()).max().unwrap_or(0);
+		if size_x == 0 || size_y == 0 {
+			return Err(MapError::Empty);
+		}
 
-			tiles[x][y] = Tile::from_char(c);
-			snowballs[x][y] = SnowBall::from_char(c);
-			if c == 'P' {
-				assert!(player_pos.is_none(), "There cannot be two player's initial positions on the map.");
-				player_pos = Some((x, y));
+		let mut tiles = vec![vec![Tile::Empty; size_y]; size_x];
+		let mut snowballs = vec![vec![None; size_y]; size_x];
+		let mut player_pos = None::<(usize, usize)>;
+
+		for (x, row) in rows.iter().enumerate() {
+			for (y, c) in row.chars().enumerate() {
+				tiles[x][y] = Tile::from_char(c);
+				snowballs[x][y] = SnowBall::from_char(c);
+				if c == 'P' {
+					if let Some((px, py)) = player_pos {
+						return Err(MapError::DuplicatePlayer {
+							first: (px + 1, py + 1),
+							second: (x + 1, y + 1),
+						});
+					}
+					player_pos = Some((x, y));
+				}
 			}
 		}
-		assert!(player_pos.is_some(), "Missing player's initial positions on the map.");
+		let player = player_pos.ok_or(MapError::MissingPlayer)?;
 
-		return Game {
+		let mut game = Game {
+			initial_tiles: tiles.clone(),
+			initial_snowballs: snowballs.clone(),
+			initial_player: player,
 			tiles,
 			snowballs,
-			player: player_pos.unwrap(),
+			player,
 			rewind_queue: VecDeque::with_capacity(64),
+			checkpoints: Vec::new(),
 			input_history: String::with_capacity(64),
+			tree: HistoryTree::new(),
+			level_entries: Vec::new(),
+			attempts: Vec::new(),
+			progress: Progress::default(),
+			events: Vec::new(),
+			levels: Levels::default(),
+			portals: Portals::default(),
 		};
+		game.levels = Levels::discover(&game);
+		game.portals = Portals::discover(&game);
+		game.apply_rule_lines(&rule_lines)?;
+		game.checkpoints.push(Checkpoint::of(&game));
+		return Ok(game);
 	}
 }
 
@@ -528,6 +799,8 @@ impl SnowBall {
 			'm' | 'M' => Some(SnowBall::Medium),
 			'l' | 'L' => Some(SnowBall::Large),
 			'x' | 'X' => Some(SnowBall::SmallOnMedium),
+			'y' | 'Y' => Some(SnowBall::SmallOnLarge),
+			'z' | 'Z' => Some(SnowBall::MediumOnLarge),
 
 			_ => None,
 		}
@@ -538,30 +811,22 @@ impl Tile {
 	fn from_char(c: char) -> Tile {
 		match c {
 			' ' => Tile::Empty,
-			'.' | 's' | 'm' | 'l' | 'x' => Tile::Dirt,
-			',' | 'S' | 'M' | 'L' | 'X' => Tile::Snow,
+			'.' | 's' | 'm' | 'l' | 'x' | 'y' | 'z' => Tile::Dirt,
+			',' | 'S' | 'M' | 'L' | 'X' | 'Y' | 'Z' => Tile::Snow,
 			'h' | '#' => Tile::Hedge,
 			't' => Tile::Tree,
 			'o' => Tile::Obstacle,
+			'i' => Tile::Ice,
+			'1'..='9' => Tile::Portal(c as u8 - b'0'),
 
 			_ => Tile::Empty,
 		}
 	}
 }
 
-
-/// Used to parse the SIZE_X and SIZE_Y global constants of game.rs
-const fn str_to_usize(s: &str) -> usize {
-	assert!(s.len() == 3);
-	const fn d_to_usize(byte: u8) -> usize {
-		assert!(0x30 <= byte && byte <= 0x39, "non-digit character");
-		return (byte & 15) as usize;
-	}
-
-	let s = s.as_bytes();
-	let unit = d_to_usize(s[s.len() - 1]);
-	let tens = 10 * d_to_usize(s[s.len() - 2]);
-	let hundreds = 100 * d_to_usize(s[s.len() - 3]);
-
-	return hundreds + tens + unit;
+/// Whether the character means something in the `map.txt` format. The others
+/// are read as empty tiles.
+pub fn is_map_char(c: char) -> bool {
+	return matches!(c, ' ' | '.' | ',' | 'h' | '#' | 't' | 'o' | 'i' | '1'..='9' | 'P')
+		|| SnowBall::from_char(c).is_some();
 }
@@ -0,0 +1,212 @@
+//!
+//! Static detection of the positions where the current level cannot be completed
+//! anymore. Every check is conservative: a reported deadlock is a certain one,
+//! but some deadlocks are not detected.
+//!
+
+use super::*;
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Deadlock {
+	/// A snowball which cannot be pushed anymore, and which cannot be the base
+	/// of a snowman. Typically a ball stuck in a corner.
+	FrozenBall { at: (usize, usize) },
+	/// Snowballs that can only meet each other, typically along a wall,
+	/// and that are not enough to build snowmen together.
+	IsolatedBalls { balls: Vec<(usize, usize)> },
+	/// The number of balls in the level is not a multiple of three.
+	BallCount { count: usize },
+	/// There are more large (or medium and large) balls than snowmen to build,
+	/// and balls never shrink.
+	BallsTooBig,
+	/// There is not enough snow left to grow the balls to the needed sizes.
+	NotEnoughSnow { missing: usize },
+}
+
+impl Deadlock {
+	/// The tiles of the snowballs involved, if the deadlock is a local one.
+	pub fn tiles(&self) -> &[(usize, usize)] {
+		match self {
+			Deadlock::FrozenBall { at } => std::slice::from_ref(at),
+			Deadlock::IsolatedBalls { balls } => balls,
+			_ => &[],
+		}
+	}
+}
+
+impl std::fmt::Display for Deadlock {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Deadlock::FrozenBall { at: (x, y) } => {
+				write!(f, "the snowball at ({x}, {y}) is stuck and cannot be part of a snowman")
+			}
+			Deadlock::IsolatedBalls { balls } => write!(
+				f,
+				"{} snowball(s) can no longer meet enough other balls to build a snowman",
+				balls.len()
+			),
+			Deadlock::BallCount { count } => {
+				write!(f, "the level has {count} balls, which is not a multiple of three")
+			}
+			Deadlock::BallsTooBig => write!(f, "too many balls have grown too big"),
+			Deadlock::NotEnoughSnow { missing } => {
+				write!(f, "{missing} more snow tile(s) would be needed to grow the balls")
+			}
+		}
+	}
+}
+
+impl Game {
+	/// The deadlocks of the level the player is in. Empty if there are none
+	/// or if the player is not in a level.
+	pub fn current_level_deadlocks(&self) -> Vec<Deadlock> {
+		match self.current_level() {
+			Some(level) => return self.level_deadlocks(&level.cells),
+			None => return Vec::new(),
+		}
+	}
+
+	pub(super) fn level_deadlocks(&self, cells: &[(usize, usize)]) -> Vec<Deadlock> {
+		let mut deadlocks = Vec::new();
+		let balls: Vec<((usize, usize), SnowBall)> = cells
+			.iter()
+			.filter_map(|&(x, y)| self.snowballs[x][y].map(|b| ((x, y), b)))
+			.collect();
+
+		// Counting the balls by size.
+		let (mut count, mut large, mut medium, mut units) = (0, 0, 0, 0);
+		for &(_, ball) in balls.iter() {
+			let sizes: &[usize] = match ball {
+				SnowBall::Small => &[1],
+				SnowBall::Medium => &[2],
+				SnowBall::Large => &[3],
+				SnowBall::SmallOnMedium => &[1, 2],
+				SnowBall::SmallOnLarge => &[1, 3],
+				SnowBall::MediumOnLarge => &[2, 3],
+				SnowBall::Snowman => &[1, 2, 3],
+			};
+			count += sizes.len();
+			large += sizes.iter().filter(|&&s| s == 3).count();
+			medium += sizes.iter().filter(|&&s| s == 2).count();
+			units += sizes.iter().sum::<usize>();
+		}
+		let snowmen = count / 3;
+		if count % 3 != 0 {
+			deadlocks.push(Deadlock::BallCount { count });
+		}
+		if large > snowmen || large + medium > 2 * snowmen {
+			deadlocks.push(Deadlock::BallsTooBig);
+		}
+		// Each push on snow grows a ball by one size at most, if the balls grow at all.
+		let grows = cells.first().is_some_and(|&cell| !self.follows(cell, Rule::NoGrowth));
+		let snow = cells
+			.iter()
+			.filter(|&&(x, y)| grows && self.tiles[x][y] == Tile::Snow)
+			.count();
+		let needed = (6 * snowmen).saturating_sub(units);
+		if snow < needed {
+			deadlocks.push(Deadlock::NotEnoughSnow {
+				missing: needed - snow,
+			});
+		}
+
+		// Where each unfinished ball could go if the other balls were not in the way.
+		let unfinished: Vec<((usize, usize), SnowBall)> = balls
+			.into_iter()
+			.filter(|&(_, ball)| ball != SnowBall::Snowman)
+			.collect();
+		let reachable: Vec<Vec<(usize, usize)>> = unfinished
+			.iter()
+			.map(|&(at, _)| self.ball_reachable_tiles(at))
+			.collect();
+
+		for (&(at, ball), tiles) in unfinished.iter().zip(reachable.iter()) {
+			let can_be_base = matches!(ball, SnowBall::Large | SnowBall::MediumOnLarge);
+			if tiles.len() == 1 && !can_be_base {
+				deadlocks.push(Deadlock::FrozenBall { at });
+			}
+		}
+
+		// Group the balls whose reachable tiles overlap, with a union-find.
+		let mut group_of: Vec<usize> = (0..unfinished.len()).collect();
+		fn root(group_of: &mut [usize], mut i: usize) -> usize {
+			while group_of[i] != i {
+				group_of[i] = group_of[group_of[i]];
+				i = group_of[i];
+			}
+			return i;
+		}
+		let mut owners: Vec<((usize, usize), usize)> = reachable
+			.iter()
+			.enumerate()
+			.flat_map(|(i, tiles)| tiles.iter().map(move |&tile| (tile, i)))
+			.collect();
+		owners.sort_unstable();
+		for pair in owners.windows(2) {
+			if pair[0].0 == pair[1].0 {
+				let (ri, rj) = (root(&mut group_of, pair[0].1), root(&mut group_of, pair[1].1));
+				group_of[ri] = rj;
+			}
+		}
+		let mut groups = vec![(0, Vec::new()); unfinished.len()];
+		for (i, &(at, ball)) in unfinished.iter().enumerate() {
+			let group = &mut groups[root(&mut group_of, i)];
+			group.0 += if ball.is_stacked() { 2 } else { 1 };
+			group.1.push(at);
+		}
+		groups.retain(|(count, _)| *count != 0);
+		// A lone group of balls is already reported by the count of the level's balls.
+		if groups.len() > 1 {
+			let mut isolated: Vec<Vec<(usize, usize)>> = groups
+				.into_iter()
+				.filter(|(count, _)| count % 3 != 0)
+				.map(|(_, mut balls)| {
+					balls.sort_unstable();
+					balls
+				})
+				.collect();
+			isolated.sort_unstable();
+			deadlocks.extend(isolated.into_iter().map(|balls| Deadlock::IsolatedBalls { balls }));
+		}
+
+		return deadlocks;
+	}
+
+	/// The tiles a snowball (or the top of a stack) could be pushed to,
+	/// ignoring the other unfinished balls, the growth on snow and the slides
+	/// on ice, which only make fewer tiles reachable.
+	fn ball_reachable_tiles(&self, from: (usize, usize)) -> Vec<(usize, usize)> {
+		let mut visited = vec![from];
+		let mut is_visited = vec![false; self.size_x() * self.size_y()];
+		is_visited[from.0 * self.size_y() + from.1] = true;
+		let mut next_to_explore = 0;
+		while let Some(&(x, y)) = visited.get(next_to_explore) {
+			next_to_explore += 1;
+			for (dir, opposite) in [
+				(Direction::Up, Direction::Down),
+				(Direction::Down, Direction::Up),
+				(Direction::Left, Direction::Right),
+				(Direction::Right, Direction::Left),
+			] {
+				let (Some(next), Some(behind)) = (self.try_step(x, y, dir), self.try_step(x, y, opposite))
+				else {
+					continue;
+				};
+				let seen = &mut is_visited[next.0 * self.size_y() + next.1];
+				if !*seen && self.can_hold_ball(next) && self.can_stand(behind) {
+					*seen = true;
+					visited.push(next);
+				}
+			}
+		}
+		return visited;
+	}
+
+	fn can_hold_ball(&self, (x, y): (usize, usize)) -> bool {
+		self.tiles[x][y].holds_snowballs() && self.snowballs[x][y] != Some(SnowBall::Snowman)
+	}
+
+	fn can_stand(&self, (x, y): (usize, usize)) -> bool {
+		!self.tiles[x][y].blocks() && self.snowballs[x][y] != Some(SnowBall::Snowman)
+	}
+}
@@ -0,0 +1,117 @@
+//!
+//! What happened in the game that a frontend may want to tell the player about.
+//! The game itself never prints anything: it queues the events, and the
+//! frontend takes them after each action to present them its own way.
+//!
+
+use super::level::LevelId;
+use super::*;
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum GameEvent {
+	/// The player could not move in that direction.
+	MoveBlocked { dir: Direction, reason: BlockReason },
+	/// A snowman now stands on the tile.
+	SnowmanBuilt { x: usize, y: usize },
+	/// The player walked onto the portal `from` and came out of `to`.
+	Teleported { from: (usize, usize), to: (usize, usize) },
+	/// The last snowman of the level was built, in `moves` moves since the player
+	/// came into the level or reset it.
+	LevelCompleted {
+		level: LevelId,
+		moves: usize,
+		/// Whether the level was never completed in fewer moves.
+		new_best: bool,
+	},
+	/// There is nothing left to rewind.
+	RewindExhausted,
+	/// There is no rewound action to redo.
+	NothingToRedo,
+	/// The player is not in a level, the level and the player are already as
+	/// they started, or the level's rules forbid it.
+	ResetRejected,
+	/// The history being loaded does not match the map. The actions from
+	/// `index` on were dropped.
+	SaveDesync { index: usize },
+	/// An action of the history tree could not be performed again, after the
+	/// first `index` actions of the history.
+	HistoryDesync { index: usize },
+}
+
+/// Why the player could not move.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum BlockReason {
+	OutsideMap,
+	/// A hedge, a tree or an obstacle is in the way.
+	Obstacle(Tile),
+	/// Snowmen cannot be pushed.
+	Snowman,
+	/// The snowball cannot be pushed in that direction.
+	StuckSnowball,
+}
+
+impl std::fmt::Display for GameEvent {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			GameEvent::MoveBlocked { reason, .. } => write!(f, "Cannot move: {reason}."),
+			GameEvent::SnowmanBuilt { .. } => write!(f, "A snowman was built."),
+			GameEvent::Teleported { .. } => write!(f, "The portal took the player away."),
+			GameEvent::LevelCompleted {
+				level,
+				moves,
+				new_best,
+			} => {
+				write!(f, "Level {} completed in {moves} moves.", level.0 + 1)?;
+				if *new_best {
+					write!(f, " New record!")?;
+				}
+				Ok(())
+			}
+			GameEvent::RewindExhausted => write!(f, "Cannot rewind any further."),
+			GameEvent::NothingToRedo => write!(f, "Nothing to redo."),
+			GameEvent::ResetRejected => write!(f, "Cannot reset the level."),
+			GameEvent::SaveDesync { index } => write!(
+				f,
+				"The save data is not coherent with the current map. Error at character of index {index}."
+			),
+			GameEvent::HistoryDesync { index } => write!(
+				f,
+				"The history is not coherent with the current map. Error after {index} actions."
+			),
+		}
+	}
+}
+
+impl std::fmt::Display for BlockReason {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			BlockReason::OutsideMap => write!(f, "that would be outside the map"),
+			BlockReason::Obstacle(Tile::Hedge) => write!(f, "a hedge is in the way"),
+			BlockReason::Obstacle(Tile::Tree) => write!(f, "a tree is in the way"),
+			BlockReason::Obstacle(_) => write!(f, "an obstacle is in the way"),
+			BlockReason::Snowman => write!(f, "snowmen cannot be pushed"),
+			BlockReason::StuckSnowball => write!(f, "the snowball cannot be pushed there"),
+		}
+	}
+}
+
+impl Game {
+	/// The events since the last call.
+	pub fn take_events(&mut self) -> Vec<GameEvent> {
+		std::mem::take(&mut self.events)
+	}
+
+	/// Why `Game::step` refuses the move.
+	pub(super) fn block_reason(&self, dir: Direction) -> BlockReason {
+		let Some((x, y)) = self.try_step(self.player.0, self.player.1, dir) else {
+			return BlockReason::OutsideMap;
+		};
+		if self.tiles[x][y].blocks() {
+			return BlockReason::Obstacle(self.tiles[x][y]);
+		}
+		match self.snowballs[x][y] {
+			Some(SnowBall::Snowman) => return BlockReason::Snowman,
+			_ => return BlockReason::StuckSnowball,
+		}
+	}
+}
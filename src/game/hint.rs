@@ -0,0 +1,165 @@
+//!
+//! Hints for the stuck player, computed with the solver.
+//!
+
+use std::time::Instant;
+
+use super::solver::{Limits, SolveError};
+use super::*;
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Hint {
+	/// Play `dir` next, moving to `target`. `solution` is the whole
+	/// sequence of moves completing the level, starting with `dir`.
+	Move {
+		dir: Direction,
+		target: (usize, usize),
+		solution: String,
+	},
+	/// Every snowball of the level is already a snowman.
+	Solved,
+	/// The level cannot be completed from the current state. It can again after
+	/// `rewinds` rewinds, if that number could be found.
+	Unsolvable { rewinds: Option<usize> },
+	/// The solver could not conclude.
+	Unknown(SolveError),
+}
+
+impl Game {
+	/// The next move of a shortest solution of the current level. The limits
+	/// are those of the whole hint, however many solves it takes.
+	pub fn hint(&self, limits: &Limits) -> Hint {
+		let deadline = limits.max_duration.map(|duration| Instant::now() + duration);
+		match self.solve_current_level(&until(limits, deadline)) {
+			Ok(solution) => {
+				let Some(dir) = solution.moves.chars().next().and_then(|c| Direction::try_from(c).ok())
+				else {
+					return Hint::Solved;
+				};
+				let target = self
+					.try_step(self.player.0, self.player.1, dir)
+					.expect("The solver stays on the map.");
+				return Hint::Move {
+					dir,
+					target,
+					solution: solution.moves,
+				};
+			}
+			Err(SolveError::Unsolvable) => {
+				return Hint::Unsolvable {
+					rewinds: self.rewinds_to_solvable(limits, deadline),
+				};
+			}
+			Err(e) => return Hint::Unknown(e),
+		}
+	}
+
+	/// The smallest number of rewinds after which the current level is solvable,
+	/// among the states where the player is still inside the current level.
+	/// Between two resets, if a state is solvable, all the ones before it are
+	/// too, hence a bisection between them, from the last reset backwards.
+	fn rewinds_to_solvable(&self, limits: &Limits, deadline: Option<Instant>) -> Option<usize> {
+		let level = self.current_level().map(|level| level.id);
+		let in_level = |game: &Game| level.is_some() && game.current_level().map(|l| l.id) == level;
+		let rewound_by = |count: usize| {
+			let mut game = self.clone();
+			for _ in 0..count {
+				game.rewind();
+			}
+			game
+		};
+
+		// How far back the player stays in the level.
+		let mut rewound = self.clone();
+		let mut max_count = 0;
+		while rewound.rewind_depth() > 0 {
+			rewound.rewind();
+			if !in_level(&rewound) {
+				break;
+			}
+			max_count += 1;
+		}
+
+		// `None` if the solver could not tell in time.
+		let is_solvable = |count: usize| match rewound_by(count).solve_current_level(&until(limits, deadline)) {
+			Ok(_) => Some(true),
+			Err(SolveError::Unsolvable) => Some(false),
+			Err(_) => None,
+		};
+		// The rewinds from `low` to `high` go back to states without a reset
+		// between them: `high` is right after a reset, or the furthest.
+		let history = self.input_history.as_bytes();
+		let mut low = 1;
+		while low <= max_count {
+			let high = (low..max_count)
+				.find(|&count| history[history.len() - count - 1] == b'T')
+				.unwrap_or(max_count);
+			if is_solvable(high)? {
+				let (mut low, mut high) = (low, high);
+				// Invariant: `high` rewinds lead to a solvable state, `low - 1` do not.
+				while low < high {
+					let middle = (low + high) / 2;
+					if is_solvable(middle)? {
+						high = middle;
+					} else {
+						low = middle + 1;
+					}
+				}
+				return Some(high);
+			}
+			low = high + 1;
+		}
+		return None;
+	}
+}
+
+/// The limits of one solve, which must end by `deadline`.
+fn until(limits: &Limits, deadline: Option<Instant>) -> Limits {
+	Limits {
+		max_duration: deadline.map(|deadline| deadline.saturating_duration_since(Instant::now())),
+		..*limits
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/// The small ball pushed next to the medium one cannot be taken back.
+	const MAP: &str = "P\n.s.ml\n";
+
+	fn play(game: &mut Game, actions: &str) {
+		for c in actions.chars() {
+			match c {
+				'T' => assert!(game.reset_current_level()),
+				_ => assert!(game.process_player_input(c.try_into().unwrap()), "{c} is blocked"),
+			}
+		}
+	}
+
+	#[test]
+	fn hint_is_the_next_move_of_a_solution() {
+		let mut game = Game::from_map_str(MAP).unwrap();
+		play(&mut game, "D");
+		let Hint::Move { dir, solution, .. } = game.hint(&Limits::default()) else {
+			panic!("The level is solvable.");
+		};
+		assert_eq!(dir, Direction::Up);
+		assert_eq!(solution, "URRDRULLLDRRR");
+	}
+
+	#[test]
+	fn rewinds_to_the_last_solvable_state() {
+		let mut game = Game::from_map_str(MAP).unwrap();
+		play(&mut game, "DRR");
+		assert_eq!(game.hint(&Limits::default()), Hint::Unsolvable { rewinds: Some(2) });
+	}
+
+	#[test]
+	fn rewinds_do_not_go_back_before_a_reset() {
+		// The states before the reset are as hopeless as the current one.
+		let mut game = Game::from_map_str(MAP).unwrap();
+		play(&mut game, "DRRTR");
+		assert_eq!(game.hint(&Limits::default()), Hint::Unsolvable { rewinds: Some(1) });
+	}
+}
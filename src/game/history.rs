@@ -0,0 +1,131 @@
+//!
+//! The rewind memory. The undo data of the most recent actions is kept as is,
+//! and a snapshot of the board is taken at regular intervals of the input
+//! history. Rewinding further than the recent actions restores the last
+//! snapshot before the target and replays the inputs from there. So the
+//! memory is unbounded, and yet it costs only a few bits per action.
+//!
+
+use super::*;
+
+/// Number of actions whose undo data is kept.
+const RECENT_UNDOS: usize = 2048;
+/// Number of actions between two checkpoints. Must not exceed `RECENT_UNDOS`,
+/// so that replaying from a checkpoint refills the recent undo data.
+const CHECKPOINT_INTERVAL: usize = 1024;
+
+/// How to revert one action.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub(super) enum Undo {
+	Step(Update),
+	/// The player's position and the tiles of the level before the reset.
+	Reset {
+		player: (usize, usize),
+		tiles: Vec<OneTileUpdate>,
+	},
+}
+
+/// The state of the board after a number of actions that is a multiple of
+/// `CHECKPOINT_INTERVAL`.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub(super) struct Checkpoint {
+	tiles: Vec<Vec<Tile>>,
+	snowballs: Vec<Vec<Option<SnowBall>>>,
+	player: (usize, usize),
+}
+
+impl Checkpoint {
+	pub(super) fn of(game: &Game) -> Checkpoint {
+		Checkpoint {
+			tiles: game.tiles.clone(),
+			snowballs: game.snowballs.clone(),
+			player: game.player,
+		}
+	}
+}
+
+impl Game {
+	/// Remember that `action` was just performed, and how to revert it.
+	pub(super) fn record(&mut self, action: char, undo: Undo) {
+		// We ain't gonna let the stack grow to out of memory and beyond.
+		if self.rewind_queue.len() >= RECENT_UNDOS {
+			self.rewind_queue.pop_front();
+		}
+		self.rewind_queue.push_back(undo);
+		self.input_history.push(action);
+
+		let len = self.input_history.len();
+		// After a snapshot was applied, the checkpoints before it are missing
+		// until a restore replays the history.
+		if len.is_multiple_of(CHECKPOINT_INTERVAL)
+			&& self.checkpoints.len() >= len / CHECKPOINT_INTERVAL
+		{
+			self.checkpoints.truncate(len / CHECKPOINT_INTERVAL);
+			self.checkpoints.push(Checkpoint::of(self));
+		}
+	}
+
+	pub fn rewind(&mut self) -> bool {
+		if self.input_history.is_empty() {
+			self.events.push(GameEvent::RewindExhausted);
+			return false;
+		}
+
+		if let Some(undo) = self.rewind_queue.pop_back() {
+			self.apply_undo(&undo);
+			self.input_history.pop();
+		} else {
+			self.restore(self.input_history.len() - 1);
+		}
+		self.retreat_in_tree();
+		self.forget_level_entries_after(self.input_history.len());
+		self.forget_attempts_after(self.input_history.len());
+		// The checkpoints of the abandoned future are not valid anymore.
+		self.checkpoints
+			.truncate(self.input_history.len() / CHECKPOINT_INTERVAL + 1);
+		return true;
+	}
+
+	/// The number of times the player can rewind.
+	pub fn rewind_depth(&self) -> usize {
+		self.input_history.len()
+	}
+
+	fn apply_undo(&mut self, undo: &Undo) {
+		match undo {
+			Undo::Step(update) => self.apply_update(update),
+			Undo::Reset { player, tiles } => {
+				for update in tiles.iter() {
+					self.apply_unit_update(update);
+				}
+				self.player = *player;
+			}
+		}
+	}
+
+	/// Bring the game back to its state after the first `len` actions
+	/// of the history, from the last checkpoint before it. After a snapshot
+	/// was applied, the checkpoints it skipped are taken on the way.
+	fn restore(&mut self, len: usize) {
+		let index = (len / CHECKPOINT_INTERVAL).min(self.checkpoints.len() - 1);
+		let checkpoint = &self.checkpoints[index];
+		self.tiles.clone_from(&checkpoint.tiles);
+		self.snowballs.clone_from(&checkpoint.snowballs);
+		self.player = checkpoint.player;
+
+		let mut history = std::mem::take(&mut self.input_history);
+		let replayed = history.split_off(index * CHECKPOINT_INTERVAL);
+		self.input_history = history;
+		self.rewind_queue.clear();
+		// The replay records them again, and a reset must not see the later ones.
+		self.forget_level_entries_after(self.input_history.len());
+		self.forget_attempts_after(self.input_history.len());
+		// Those actions are not news for the player.
+		let events = self.events.len();
+		for action in replayed[..len - index * CHECKPOINT_INTERVAL].chars() {
+			let action_worked = self.perform(action);
+			debug_assert!(action_worked, "The history replays identically.");
+		}
+		self.events.truncate(events);
+	}
+}
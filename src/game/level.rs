@@ -0,0 +1,218 @@
+//!
+//! The levels of the map: the connected regions of dirt and snow on the map as
+//! it was loaded. They are found once when the map is loaded, then any tile can
+//! be looked up to know which level it belongs to.
+//!
+
+use super::reset::LevelDfsExplorer;
+use super::rule::Rule;
+use super::*;
+
+/// The index of a level in `Levels`. Levels are numbered in the reading order
+/// of their top-left tile.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub struct LevelId(pub usize);
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Level {
+	pub id: LevelId,
+	pub name: String,
+	/// The smallest coordinates of the level's tiles, then the largest ones.
+	pub bounds: ((usize, usize), (usize, usize)),
+	/// The level's tiles, sorted.
+	pub cells: Vec<(usize, usize)>,
+	/// The snowballs of the level as it was loaded.
+	pub snowballs: Vec<((usize, usize), SnowBall)>,
+	/// The level's tiles next to an empty tile or a portal, where the player can come in.
+	pub doors: Vec<(usize, usize)>,
+	/// The number of snowmen to build to complete the level.
+	pub required_snowmen: usize,
+	/// The rules of the map for this level only, sorted.
+	pub rules: Vec<Rule>,
+}
+
+impl Level {
+	/// The name of the level, with its rules if it has some.
+	pub fn title(&self) -> String {
+		if self.rules.is_empty() {
+			return self.name.clone();
+		}
+		let rules: Vec<&str> = self.rules.iter().map(|rule| rule.name()).collect();
+		return format!("{} ({})", self.name, rules.join(", "));
+	}
+
+	pub fn contains(&self, cell: (usize, usize)) -> bool {
+		let ((min_x, min_y), (max_x, max_y)) = self.bounds;
+		(min_x..=max_x).contains(&cell.0)
+			&& (min_y..=max_y).contains(&cell.1)
+			&& self.cells.binary_search(&cell).is_ok()
+	}
+}
+
+/// All the levels of a map.
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct Levels {
+	levels: Vec<Level>,
+	/// The level of each tile of the map, indexed like `Game::tiles`.
+	at: Vec<Vec<Option<LevelId>>>,
+}
+
+impl Levels {
+	/// Find the levels of the game's initial map.
+	pub(super) fn discover(game: &Game) -> Levels {
+		let mut levels = Vec::new();
+		let mut at = vec![vec![None; game.size_y()]; game.size_x()];
+		for x in 0..game.size_x() {
+			for y in 0..game.size_y() {
+				if at[x][y].is_some() {
+					continue;
+				}
+				let mut cells: Vec<(usize, usize)> = LevelDfsExplorer::from_point(game, x, y).collect();
+				if cells.is_empty() {
+					continue;
+				}
+				cells.sort_unstable();
+				let id = LevelId(levels.len());
+				for &(cx, cy) in cells.iter() {
+					at[cx][cy] = Some(id);
+				}
+				levels.push(Level::new(game, id, cells));
+			}
+		}
+		return Levels { levels, at };
+	}
+
+	pub fn len(&self) -> usize {
+		self.levels.len()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.levels.is_empty()
+	}
+
+	pub fn iter(&self) -> std::slice::Iter<'_, Level> {
+		self.levels.iter()
+	}
+
+	pub fn get(&self, id: LevelId) -> Option<&Level> {
+		self.levels.get(id.0)
+	}
+
+	/// The level the tile belongs to, if any.
+	pub fn at(&self, (x, y): (usize, usize)) -> Option<&Level> {
+		let id = (*self.at.get(x)?.get(y)?)?;
+		return Some(&self.levels[id.0]);
+	}
+
+	pub(super) fn add_rules(&mut self, id: LevelId, rules: &[Rule]) {
+		let level = &mut self.levels[id.0];
+		level.rules.extend_from_slice(rules);
+		level.rules.sort_unstable();
+		level.rules.dedup();
+	}
+
+	/// The total number of snowmen to build on the map.
+	pub fn required_snowmen(&self) -> usize {
+		self.levels.iter().map(|level| level.required_snowmen).sum()
+	}
+}
+
+impl Level {
+	fn new(game: &Game, id: LevelId, cells: Vec<(usize, usize)>) -> Level {
+		let min = cells.iter().fold((usize::MAX, usize::MAX), |(a, b), &(x, y)| (a.min(x), b.min(y)));
+		let max = cells.iter().fold((0, 0), |(a, b), &(x, y)| (a.max(x), b.max(y)));
+		let snowballs: Vec<((usize, usize), SnowBall)> = cells
+			.iter()
+			.filter_map(|&(x, y)| Some(((x, y), game.initial_snowballs[x][y]?)))
+			.collect();
+		let doors = cells
+			.iter()
+			.copied()
+			.filter(|&(x, y)| {
+				[Direction::Up, Direction::Left, Direction::Down, Direction::Right]
+					.into_iter()
+					.filter_map(|dir| game.try_step(x, y, dir))
+					.any(|(nx, ny)| matches!(game.initial_tiles[nx][ny], Tile::Empty | Tile::Portal(_)))
+			})
+			.collect();
+		let balls: usize = snowballs.iter().map(|&(_, snowball)| snowball.ball_count()).sum();
+		return Level {
+			id,
+			name: format!("Level {}", id.0 + 1),
+			bounds: (min, max),
+			cells,
+			snowballs,
+			doors,
+			required_snowmen: balls / 3,
+			rules: Vec::new(),
+		};
+	}
+}
+
+impl Game {
+	pub fn levels(&self) -> &Levels {
+		&self.levels
+	}
+
+	/// The level the player is in, if any.
+	pub fn current_level(&self) -> Option<&Level> {
+		self.levels.at(self.player)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/// Two levels, walled by hedges, each with one snowman to build.
+	const MAP: &str = "P  # \n.sx#,\n#..#X\n####m\n";
+
+	#[test]
+	fn levels_are_found_in_reading_order() {
+		let game = Game::from_map_str(MAP).unwrap();
+		let levels = game.levels();
+		assert_eq!(levels.len(), 2);
+		let first = levels.get(LevelId(0)).unwrap();
+		assert_eq!(first.name, "Level 1");
+		assert_eq!(first.cells, [(1, 0), (1, 1), (1, 2), (2, 1), (2, 2)]);
+		assert_eq!(first.bounds, ((1, 0), (2, 2)));
+		assert_eq!(first.snowballs, [((1, 1), SnowBall::Small), ((1, 2), SnowBall::SmallOnMedium)]);
+		let second = levels.get(LevelId(1)).unwrap();
+		assert_eq!(second.cells, [(1, 4), (2, 4), (3, 4)]);
+		assert!(levels.get(LevelId(2)).is_none());
+	}
+
+	#[test]
+	fn tiles_tell_their_level() {
+		let game = Game::from_map_str(MAP).unwrap();
+		let levels = game.levels();
+		assert_eq!(levels.at((2, 2)).map(|level| level.id), Some(LevelId(0)));
+		assert_eq!(levels.at((3, 4)).map(|level| level.id), Some(LevelId(1)));
+		assert!(levels.at((0, 0)).is_none());
+		assert!(levels.at((2, 3)).is_none());
+		assert!(levels.at((9, 9)).is_none());
+		assert!(levels.get(LevelId(0)).unwrap().contains((2, 1)));
+		assert!(!levels.get(LevelId(0)).unwrap().contains((2, 0)));
+		assert!(game.current_level().is_none());
+	}
+
+	#[test]
+	fn doors_are_next_to_empty_tiles() {
+		let game = Game::from_map_str(MAP).unwrap();
+		let levels = game.levels();
+		assert_eq!(levels.get(LevelId(0)).unwrap().doors, [(1, 0), (1, 1), (1, 2)]);
+		assert_eq!(levels.get(LevelId(1)).unwrap().doors, [(1, 4)]);
+		// A portal is a way in too.
+		let game = Game::from_map_str("P1#1\n#.#s\n").unwrap();
+		assert_eq!(game.levels().get(LevelId(1)).unwrap().doors, [(1, 3)]);
+	}
+
+	#[test]
+	fn snowmen_to_build_count_the_balls_of_each_level() {
+		let game = Game::from_map_str(MAP).unwrap();
+		assert_eq!(game.levels().iter().map(|level| level.required_snowmen).collect::<Vec<_>>(), [1, 1]);
+		assert_eq!(game.levels().required_snowmen(), 2);
+		let game = Game::from_map_str("P  \n.sm\n").unwrap();
+		assert_eq!(game.levels().required_snowmen(), 0);
+	}
+}
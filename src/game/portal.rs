@@ -0,0 +1,53 @@
+//!
+//! The portals of the map: the two tiles of the same number are linked, and
+//! the player walking onto one comes out of the other. They are paired once
+//! when the map is loaded, since the tiles never change into or from portals.
+//!
+
+use super::*;
+
+/// The linked portals of a map.
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct Portals {
+	/// The other end of each portal, indexed like `Game::tiles`.
+	exits: Vec<Vec<Option<(usize, usize)>>>,
+}
+
+impl Portals {
+	/// Pair the portals of the game's initial map. A number on one tile only,
+	/// or on more than two, links nothing.
+	pub(super) fn discover(game: &Game) -> Portals {
+		let mut exits = vec![vec![None; game.size_y()]; game.size_x()];
+		for ends in Portals::ends(&game.initial_tiles) {
+			if let [a, b] = ends[..] {
+				exits[a.0][a.1] = Some(b);
+				exits[b.0][b.1] = Some(a);
+			}
+		}
+		return Portals { exits };
+	}
+
+	/// The tiles of each portal number, from 1 to 9, in the reading order.
+	pub fn ends(tiles: &[Vec<Tile>]) -> [Vec<(usize, usize)>; 9] {
+		let mut ends: [Vec<(usize, usize)>; 9] = Default::default();
+		for (x, row) in tiles.iter().enumerate() {
+			for (y, &tile) in row.iter().enumerate() {
+				if let Tile::Portal(number @ 1..=9) = tile {
+					ends[number as usize - 1].push((x, y));
+				}
+			}
+		}
+		return ends;
+	}
+
+	/// Where the player comes out when walking onto the tile, if it's a linked portal.
+	pub fn exit(&self, (x, y): (usize, usize)) -> Option<(usize, usize)> {
+		*self.exits.get(x)?.get(y)?
+	}
+}
+
+impl Game {
+	pub fn portals(&self) -> &Portals {
+		&self.portals
+	}
+}
@@ -0,0 +1,234 @@
+//!
+//! The player's achievements: which levels they completed, and in how many
+//! moves. Unlike the board, the records are not affected by rewinds.
+//!
+//! The moves of a completion are counted from the start of the attempt, that is
+//! the first time the player came into the level since they last reset or
+//! completed it. The move into the level counts, it may well push a ball; the
+//! reset does not.
+//!
+
+use std::collections::BTreeMap;
+
+use super::level::LevelId;
+use super::*;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct LevelRecord {
+	/// The number of moves of the first completion of the level.
+	pub first_moves: usize,
+	/// The fewest moves the level was ever completed in.
+	pub best_moves: usize,
+}
+
+/// The actions of the history between which the player tried to complete a level.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(super) struct Attempt {
+	pub level: LevelId,
+	/// The index of the action which started the attempt.
+	pub start: usize,
+	/// The index of the action which reset or completed the level, if any yet.
+	pub end: Option<usize>,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct Progress {
+	records: BTreeMap<LevelId, LevelRecord>,
+}
+
+impl Progress {
+	pub fn record(&self, level: LevelId) -> Option<LevelRecord> {
+		self.records.get(&level).copied()
+	}
+
+	/// The completed levels and their records, in the order of the levels.
+	pub fn records(&self) -> impl Iterator<Item = (LevelId, LevelRecord)> + '_ {
+		self.records.iter().map(|(&level, &record)| (level, record))
+	}
+
+	pub fn completed_count(&self) -> usize {
+		self.records.len()
+	}
+
+	/// Remember a completion of the level. Return whether it's a new best.
+	pub fn complete(&mut self, level: LevelId, moves: usize) -> bool {
+		match self.records.get_mut(&level) {
+			Some(record) if record.best_moves <= moves => return false,
+			Some(record) => record.best_moves = moves,
+			None => {
+				self.records.insert(
+					level,
+					LevelRecord {
+						first_moves: moves,
+						best_moves: moves,
+					},
+				);
+			}
+		}
+		return true;
+	}
+
+	/// Bring back a record, like the ones of a save file.
+	pub fn insert(&mut self, level: LevelId, record: LevelRecord) {
+		self.records.insert(level, record);
+	}
+}
+
+impl Game {
+	pub fn progress(&self) -> &Progress {
+		&self.progress
+	}
+
+	pub fn progress_mut(&mut self) -> &mut Progress {
+		&mut self.progress
+	}
+
+	/// Whether every snowball of the level is a snowman.
+	pub fn is_level_complete(&self, id: LevelId) -> bool {
+		let Some(level) = self.levels.get(id) else {
+			return false;
+		};
+		level.required_snowmen > 0
+			&& level
+				.cells
+				.iter()
+				.all(|&(x, y)| matches!(self.snowballs[x][y], None | Some(SnowBall::Snowman)))
+	}
+
+	/// The number of snowmen built on the whole map.
+	pub fn snowmen_count(&self) -> usize {
+		self.snowballs
+			.iter()
+			.flatten()
+			.filter(|&&snowball| snowball == Some(SnowBall::Snowman))
+			.count()
+	}
+
+	/// The level the player will be in after the action, if it's not complete yet.
+	pub(super) fn level_to_complete(&self, action: char) -> Option<LevelId> {
+		let dir = Direction::try_from(action).ok()?;
+		let target = self.try_step(self.player.0, self.player.1, dir)?;
+		let id = self.levels.at(target)?.id;
+		return (!self.is_level_complete(id)).then_some(id);
+	}
+
+	/// Called after an action of the player: record the completion of the
+	/// level if the action just completed it.
+	pub(super) fn check_completion(&mut self, level: Option<LevelId>) {
+		let Some(id) = level else {
+			return;
+		};
+		if self.current_level().map(|l| l.id) != Some(id) || !self.is_level_complete(id) {
+			return;
+		}
+		let last = self.input_history.len() - 1;
+		let start = match self.open_attempt(id) {
+			Some(attempt) => {
+				attempt.end = Some(last);
+				attempt.start
+			}
+			None => 0,
+		};
+		let moves = self.input_history[start..].chars().filter(|&c| c != 'T').count();
+		let new_best = self.progress.complete(id, moves);
+		self.events.push(GameEvent::LevelCompleted {
+			level: id,
+			moves,
+			new_best,
+		});
+	}
+
+	/// The attempt at the level which was not reset nor completed yet.
+	fn open_attempt(&mut self, level: LevelId) -> Option<&mut Attempt> {
+		self.attempts
+			.iter_mut()
+			.rev()
+			.find(|attempt| attempt.level == level && attempt.end.is_none())
+	}
+
+	/// Called when the player comes into a level, at the action of index `index`.
+	/// The attempt in progress at that level, if any, goes on.
+	pub(super) fn continue_attempt(&mut self, level: LevelId, index: usize) {
+		if self.open_attempt(level).is_none() {
+			self.attempts.push(Attempt {
+				level,
+				start: index,
+				end: None,
+			});
+		}
+	}
+
+	/// Called when the player resets a level, at the action of index `index`.
+	pub(super) fn restart_attempt(&mut self, level: LevelId, index: usize) {
+		if let Some(attempt) = self.open_attempt(level) {
+			attempt.end = Some(index);
+		}
+		self.attempts.push(Attempt {
+			level,
+			start: index,
+			end: None,
+		});
+	}
+
+	/// Forget the starts and ends of attempts after the first `len` actions,
+	/// which have been rewound.
+	pub(super) fn forget_attempts_after(&mut self, len: usize) {
+		self.attempts.retain(|attempt| attempt.start < len);
+		for attempt in self.attempts.iter_mut() {
+			if attempt.end.is_some_and(|end| end >= len) {
+				attempt.end = None;
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/// The balls are stacked from above the level, where the player goes in and out.
+	const MAP: &str = "P    \n s.  \n m.  \n l.  \n";
+
+	fn play(game: &mut Game, actions: &str) -> Vec<GameEvent> {
+		for c in actions.chars() {
+			match c {
+				'T' => assert!(game.reset_current_level()),
+				_ => assert!(game.process_player_input(c.try_into().unwrap()), "{c} is blocked"),
+			}
+		}
+		game.take_events()
+	}
+
+	fn completion(events: &[GameEvent]) -> Option<(usize, bool)> {
+		events.iter().find_map(|event| match *event {
+			GameEvent::LevelCompleted { moves, new_best, .. } => Some((moves, new_best)),
+			_ => None,
+		})
+	}
+
+	#[test]
+	fn attempt_goes_on_when_coming_back_into_the_level() {
+		let mut game = Game::from_map_str(MAP).unwrap();
+		// Counted from the move into the level, the second one, which pushes a ball.
+		assert_eq!(completion(&play(&mut game, "DRDUURDRDLULD")), Some((12, true)));
+		assert_eq!(game.progress().record(LevelId(0)).map(|r| r.best_moves), Some(12));
+	}
+
+	#[cfg(feature = "solver")]
+	#[test]
+	fn reset_starts_a_new_attempt() {
+		let mut game = Game::from_map_str(MAP).unwrap();
+		play(&mut game, "DRDUT");
+		let solution = game.solve_current_level(&solver::Limits::default()).unwrap().moves;
+		assert_eq!(completion(&play(&mut game, &solution)), Some((solution.len(), true)));
+	}
+
+	#[test]
+	fn rewound_completion_counts_from_the_same_start() {
+		let mut game = Game::from_map_str(MAP).unwrap();
+		play(&mut game, "DRDUURDRDLULD");
+		assert!(game.rewind());
+		game.take_events();
+		assert_eq!(completion(&play(&mut game, "D")), Some((12, false)));
+	}
+}
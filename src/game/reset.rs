@@ -1,50 +1,118 @@
 //!
 //! Reset a unique level.
 //!
+//! The player is put back where they first entered the level, or on the
+//! nearest free tile if a snowball of the initial level is there.
+//!
 
-use std::collections::HashSet;
+use std::collections::{HashSet, VecDeque};
 
 use super::*;
 
 impl Game {
 	pub fn current_level_diff(&self) -> Vec<super::OneTileUpdate> {
-		LevelDfsExplorer::from_point(self.player.0, self.player.1)
-			.filter_map(|(x, y)| try_generate_update_at(self, x, y))
+		let Some(level) = self.current_level() else {
+			return Vec::new();
+		};
+		level
+			.cells
+			.iter()
+			.filter_map(|&(x, y)| try_generate_update_at(self, x, y))
 			.collect()
 	}
+
+	/// Start an attempt at the level if the player just stepped into it from
+	/// outside, unless one is in progress. Record their position as the
+	/// level's entry if it's the first time at that tile.
+	pub(super) fn note_level_entry(&mut self, from: (usize, usize)) {
+		if self.levels.at(from).is_some() {
+			return;
+		}
+		let Some(level) = self.current_level().map(|level| level.id) else {
+			return;
+		};
+		self.continue_attempt(level, self.input_history.len());
+		if self.level_entries.iter().all(|&(entry, _)| entry != self.player) {
+			self.level_entries.push((self.player, self.input_history.len()));
+		}
+	}
+
+	/// Forget the entries recorded after the first `len` actions, which have been rewound.
+	pub(super) fn forget_level_entries_after(&mut self, len: usize) {
+		self.level_entries.retain(|&(_, index)| index < len);
+	}
+
+	/// Where the player should stand once the current level is reset: the first
+	/// entry of the level that has no snowball in the initial level, otherwise
+	/// the free tile the nearest to the first entry. Without any recorded entry,
+	/// the player's start on the map or their current position is used instead.
+	/// Is `None` if the player is not in a level or the level is full of snowballs.
+	pub(super) fn start_after_reset(&self) -> Option<(usize, usize)> {
+		let level = self.current_level()?;
+		let is_free = |(x, y): (usize, usize)| self.initial_snowballs[x][y].is_none();
+
+		let mut entries = self
+			.level_entries
+			.iter()
+			.map(|&(entry, _)| entry)
+			.chain(std::iter::once(self.initial_player))
+			.filter(|&entry| level.contains(entry))
+			.peekable();
+		let first = *entries.peek().unwrap_or(&self.player);
+		if let Some(entry) = entries.find(|&entry| is_free(entry)) {
+			return Some(entry);
+		}
+
+		// Relocate on the nearest tile without a snowball.
+		let mut to_explore = VecDeque::from([first]);
+		let mut visited = HashSet::from([first]);
+		while let Some((x, y)) = to_explore.pop_front() {
+			if is_free((x, y)) {
+				return Some((x, y));
+			}
+			for next in valid_neighbours(self, x, y) {
+				if visited.insert(next) {
+					to_explore.push_back(next);
+				}
+			}
+		}
+		return None;
+	}
 }
 
 /// Iterate over all the tile coords `(x, y)` of the game's
 /// current level. Is empty if the player is not in a level.
-struct LevelDfsExplorer {
+pub(super) struct LevelDfsExplorer<'a> {
+	game: &'a Game,
 	to_explore: Vec<(usize, usize)>,
 	visited: HashSet<(usize, usize)>,
 }
 // It's more a flood algo than a DFS. FLOOD is more accurate.
 
-impl LevelDfsExplorer {
-	fn from_point(root_x: usize, root_y: usize) -> LevelDfsExplorer {
+impl<'a> LevelDfsExplorer<'a> {
+	pub(super) fn from_point(game: &'a Game, root_x: usize, root_y: usize) -> LevelDfsExplorer<'a> {
 		let mut to_explore = Vec::<(usize, usize)>::with_capacity(32);
 		let mut visited = HashSet::<(usize, usize)>::with_capacity(32);
 
-		if is_level_char(root_x, root_y) {
+		if is_level_tile(game, root_x, root_y) {
 			to_explore.push((root_x, root_y));
 			visited.insert((root_x, root_y));
 		} // If that `if` is not executed, the iterator won't output anything.
 
 		LevelDfsExplorer {
+			game,
 			to_explore,
 			visited,
 		}
 	}
 }
 
-impl Iterator for LevelDfsExplorer {
+impl Iterator for LevelDfsExplorer<'_> {
 	type Item = (usize, usize);
 	// The DFS algorithm is probably less readable with this iterator paradigm.
 	fn next(&mut self) -> Option<Self::Item> {
 		if let Some((x, y)) = self.to_explore.pop() {
-			for (nx, ny) in valid_neighbours(x, y) {
+			for (nx, ny) in valid_neighbours(self.game, x, y) {
 				if self.visited.insert((nx, ny)) {
 					self.to_explore.push((nx, ny));
 				}
@@ -56,19 +124,14 @@ impl Iterator for LevelDfsExplorer {
 	}
 }
 
-impl std::iter::FusedIterator for LevelDfsExplorer {}
-
-fn get_char(x: usize, y: usize) -> char {
-	let index = x * (SIZE_Y + 1) + y; // take the '\n' characters into account
-	return (*MAP.as_bytes().get(index).unwrap()).into();
-}
+impl std::iter::FusedIterator for LevelDfsExplorer<'_> {}
 
-fn is_level_char(x: usize, y: usize) -> bool {
-	let c = get_char(x, y);
-	matches!(Tile::from_char(c), Tile::Dirt | Tile::Snow)
+/// Whether the tile belonged to a level on the map as it was loaded.
+fn is_level_tile(game: &Game, x: usize, y: usize) -> bool {
+	game.initial_tiles[x][y].holds_snowballs()
 }
 
-fn valid_neighbours(x: usize, y: usize) -> impl Iterator<Item = (usize, usize)> {
+fn valid_neighbours(game: &Game, x: usize, y: usize) -> impl Iterator<Item = (usize, usize)> + '_ {
 	[
 		Direction::Up,
 		Direction::Down,
@@ -76,14 +139,13 @@ fn valid_neighbours(x: usize, y: usize) -> impl Iterator<Item = (usize, usize)>
 		Direction::Right,
 	]
 	.into_iter()
-	.filter_map(move |d| try_step(x, y, d))
-	.filter(|(xx, yy)| is_level_char(*xx, *yy))
+	.filter_map(move |d| game.try_step(x, y, d))
+	.filter(|(xx, yy)| is_level_tile(game, *xx, *yy))
 }
 
 fn try_generate_update_at(game: &Game, x: usize, y: usize) -> Option<OneTileUpdate> {
-	let c = get_char(x, y);
-	let tile = Tile::from_char(c);
-	let snowball = SnowBall::from_char(c);
+	let tile = game.initial_tiles[x][y];
+	let snowball = game.initial_snowballs[x][y];
 
 	let new_tile = (game.tiles[x][y] != tile).then_some(tile);
 	let new_snowball = (game.snowballs[x][y] != snowball).then_some(snowball);
@@ -116,7 +178,7 @@ fn try_generate_update_at(game: &Game, x: usize, y: usize) -> Option<OneTileUpda
 /* impl Game {
 pub fn current_level_diff(&self) -> Vec<super::OneTileUpdate> {
 	let (root_x, root_y) = self.player;
-	if !is_level_char(root_x, root_y) {
+	if !is_level_tile(self, root_x, root_y) {
 		return Vec::new();
 	}
 	// Simple graph search. This will be an unoptimized depth-first search.
@@ -133,7 +195,7 @@ pub fn current_level_diff(&self) -> Vec<super::OneTileUpdate> {
 			updates.push(u);
 		}
 
-		for (nx, ny) in valid_neighbours(x, y) {
+		for (nx, ny) in valid_neighbours(self, x, y) {
 			if visited.insert((nx, ny)) {
 				to_explore.push((nx, ny));
 			}
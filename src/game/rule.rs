@@ -0,0 +1,125 @@
+//!
+//! Rules of the game which only apply to some levels, like the bonus levels
+//! of the original game. They are given after the rows of the map, one line
+//! per level: `!level <line>:<column> <rule>...`, where the tile at that line
+//! and column, starting at 1, is any tile of the level.
+//!
+
+use super::level::LevelId;
+use super::*;
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub enum Rule {
+	/// The snowballs do not grow on snow, which stays there.
+	NoGrowth,
+	/// The level cannot be reset.
+	NoReset,
+}
+
+impl Rule {
+	pub const ALL: [Rule; 2] = [Rule::NoGrowth, Rule::NoReset];
+
+	/// The name of the rule in the map.
+	pub fn name(self) -> &'static str {
+		match self {
+			Rule::NoGrowth => "no-growth",
+			Rule::NoReset => "no-reset",
+		}
+	}
+
+	pub fn from_name(name: &str) -> Option<Rule> {
+		Rule::ALL.into_iter().find(|rule| rule.name() == name)
+	}
+
+	fn code(self) -> u8 {
+		match self {
+			Rule::NoGrowth => 1,
+			Rule::NoReset => 2,
+		}
+	}
+}
+
+impl std::fmt::Display for Rule {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(f, "{}", self.name())
+	}
+}
+
+/// The rows of the map, then its rule lines with their index in the map.
+/// The rules start at the first line beginning with `!`, the empty lines
+/// after it are skipped.
+pub fn split_map(map: &str) -> (Vec<&str>, Vec<(usize, &str)>) {
+	let lines: Vec<&str> = map.lines().collect();
+	let end = lines.iter().position(|line| line.starts_with('!')).unwrap_or(lines.len());
+	let rules = (end..lines.len())
+		.map(|index| (index, lines[index]))
+		.filter(|(_, line)| !line.trim().is_empty())
+		.collect();
+	return (lines[..end].to_vec(), rules);
+}
+
+/// Read a rule line: the tile it designates the level of, and the rules.
+pub fn parse_line(line: &str) -> Result<((usize, usize), Vec<Rule>), String> {
+	let usage = || format!("expected `!level <line>:<column> <rule>...`, found {line:?}");
+	let mut words = line.strip_prefix("!level ").ok_or_else(usage)?.split_whitespace();
+	let (x, y) = words.next().and_then(|at| at.split_once(':')).ok_or_else(usage)?;
+	let (Ok(x @ 1..), Ok(y @ 1..)) = (x.parse::<usize>(), y.parse::<usize>()) else {
+		return Err(usage());
+	};
+	let mut rules = Vec::new();
+	for word in words {
+		let rule = Rule::from_name(word).ok_or_else(|| {
+			let names: Vec<&str> = Rule::ALL.iter().map(|rule| rule.name()).collect();
+			format!("unknown rule {word:?}, the rules are {}", names.join(", "))
+		})?;
+		rules.push(rule);
+	}
+	if rules.is_empty() {
+		return Err(usage());
+	}
+	return Ok(((x - 1, y - 1), rules));
+}
+
+/// The rule line of the level which has the tile `(x, y)`.
+pub fn format_line((x, y): (usize, usize), rules: &[Rule]) -> String {
+	let mut line = format!("!level {}:{}", x + 1, y + 1);
+	for rule in rules {
+		line.push(' ');
+		line.push_str(rule.name());
+	}
+	return line;
+}
+
+impl Game {
+	/// Whether the tile is part of a level which follows the rule.
+	pub fn follows(&self, cell: (usize, usize), rule: Rule) -> bool {
+		self.levels.at(cell).is_some_and(|level| level.rules.contains(&rule))
+	}
+
+	/// Give the levels the rules of the map's rule lines.
+	pub(super) fn apply_rule_lines(&mut self, lines: &[(usize, &str)]) -> Result<(), MapError> {
+		for &(index, line) in lines {
+			let invalid = |message| MapError::InvalidRule {
+				line: index + 1,
+				message,
+			};
+			let (cell, rules) = parse_line(line).map_err(invalid)?;
+			let Some(level) = self.levels.at(cell).map(|level| level.id) else {
+				return Err(invalid(format!("no level at {}:{}", cell.0 + 1, cell.1 + 1)));
+			};
+			self.levels.add_rules(level, &rules);
+		}
+		return Ok(());
+	}
+
+	/// The bytes the rules add to `Game::map_hash`, none without rules.
+	pub(super) fn rule_codes(&self) -> Vec<u8> {
+		let mut codes = Vec::new();
+		for level in self.levels.iter().filter(|level| !level.rules.is_empty()) {
+			let LevelId(id) = level.id;
+			codes.extend_from_slice(&(id as u32).to_le_bytes());
+			codes.extend(level.rules.iter().map(|rule| rule.code()));
+		}
+		return codes;
+	}
+}
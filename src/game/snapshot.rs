@@ -0,0 +1,307 @@
+//!
+//! The whole state of the board after the history, to load a game without
+//! replaying it. The checkpoints and the undo data of the rewind are rebuilt
+//! by replaying the history only when the player rewinds.
+//!
+
+use super::level::LevelId;
+use super::progress::Attempt;
+use super::*;
+
+/// The board, the player and what the resets and the completions need.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Snapshot {
+	/// The `history_hash` of the history the snapshot was taken after.
+	history: u64,
+	tiles: Vec<Vec<Tile>>,
+	snowballs: Vec<Vec<Option<SnowBall>>>,
+	player: (usize, usize),
+	level_entries: Vec<((usize, usize), usize)>,
+	attempts: Vec<Attempt>,
+}
+
+/// Why a snapshot cannot be used.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum SnapshotError {
+	/// A line of the snapshot is malformed.
+	Invalid(String),
+	/// The snapshot is not of this map, or not after this history.
+	Mismatch,
+}
+
+impl std::fmt::Display for SnapshotError {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			SnapshotError::Invalid(line) => write!(f, "invalid snapshot line: {line:?}"),
+			SnapshotError::Mismatch => write!(f, "the snapshot does not match the history"),
+		}
+	}
+}
+
+impl std::error::Error for SnapshotError {}
+
+impl Snapshot {
+	/// One line per row of the board, where each cell is its tile's character
+	/// then its snowball's, after lines `history`, `player`, `entries` and
+	/// `attempts`. An attempt is its level, its start then its end if it has
+	/// one, separated by commas.
+	pub fn serialize(&self) -> String {
+		let mut text = format!("history {:016x}\n", self.history);
+		text.push_str(&format!("player {} {}\nentries", self.player.0, self.player.1));
+		for &((x, y), index) in &self.level_entries {
+			text.push_str(&format!(" {x},{y},{index}"));
+		}
+		text.push_str("\nattempts");
+		for attempt in &self.attempts {
+			text.push_str(&format!(" {},{}", attempt.level.0, attempt.start));
+			if let Some(end) = attempt.end {
+				text.push_str(&format!(",{end}"));
+			}
+		}
+		for (tiles, snowballs) in self.tiles.iter().zip(&self.snowballs) {
+			text.push_str("\nrow ");
+			for (&tile, &snowball) in tiles.iter().zip(snowballs) {
+				text.push(tile_char(tile));
+				text.push(snowball_char(snowball));
+			}
+		}
+		return text;
+	}
+
+	/// Parse the lines written by `serialize`.
+	pub fn parse<'a, I: IntoIterator<Item = &'a str>>(lines: I) -> Result<Snapshot, SnapshotError> {
+		let mut history = None;
+		let mut snapshot = Snapshot {
+			history: 0,
+			tiles: Vec::new(),
+			snowballs: Vec::new(),
+			player: (0, 0),
+			level_entries: Vec::new(),
+			attempts: Vec::new(),
+		};
+		for line in lines {
+			let invalid = || SnapshotError::Invalid(line.to_owned());
+			let (key, values) = line.split_once(' ').unwrap_or((line, ""));
+			let mut numbers = values
+				.split([' ', ','])
+				.filter(|n| !n.is_empty())
+				.map(|n| n.parse::<usize>().map_err(|_| invalid()));
+			match key {
+				"history" => history = Some(u64::from_str_radix(values, 16).map_err(|_| invalid())?),
+				"player" => {
+					let x = numbers.next().ok_or_else(invalid)??;
+					let y = numbers.next().ok_or_else(invalid)??;
+					snapshot.player = (x, y);
+				}
+				"entries" => {
+					let numbers = numbers.collect::<Result<Vec<_>, _>>()?;
+					if !numbers.len().is_multiple_of(3) {
+						return Err(invalid());
+					}
+					snapshot.level_entries = numbers.chunks(3).map(|n| ((n[0], n[1]), n[2])).collect();
+				}
+				"attempts" => {
+					for attempt in values.split(' ').filter(|a| !a.is_empty()) {
+						let numbers = attempt
+							.split(',')
+							.map(|n| n.parse::<usize>().map_err(|_| invalid()))
+							.collect::<Result<Vec<_>, _>>()?;
+						let (level, start, end) = match numbers[..] {
+							[level, start] => (level, start, None),
+							[level, start, end] => (level, start, Some(end)),
+							_ => return Err(invalid()),
+						};
+						snapshot.attempts.push(Attempt {
+							level: LevelId(level),
+							start,
+							end,
+						});
+					}
+				}
+				"row" => {
+					let cells: Vec<char> = values.chars().collect();
+					if !cells.len().is_multiple_of(2) {
+						return Err(invalid());
+					}
+					let mut tiles = Vec::with_capacity(cells.len() / 2);
+					let mut snowballs = Vec::with_capacity(cells.len() / 2);
+					for cell in cells.chunks(2) {
+						tiles.push(char_tile(cell[0]).ok_or_else(invalid)?);
+						snowballs.push(char_snowball(cell[1]).ok_or_else(invalid)?);
+					}
+					snapshot.tiles.push(tiles);
+					snapshot.snowballs.push(snowballs);
+				}
+				_ => return Err(invalid()),
+			}
+		}
+		snapshot.history = history.ok_or_else(|| SnapshotError::Invalid("missing history".to_owned()))?;
+		return Ok(snapshot);
+	}
+}
+
+impl Game {
+	pub fn snapshot(&self) -> Snapshot {
+		Snapshot {
+			history: history_hash(&self.input_history),
+			tiles: self.tiles.clone(),
+			snowballs: self.snowballs.clone(),
+			player: self.player,
+			level_entries: self.level_entries.clone(),
+			attempts: self.attempts.clone(),
+		}
+	}
+
+	/// Play the history by putting the board in the state of the snapshot
+	/// taken after it, on a game where nothing was played yet.
+	pub fn apply_snapshot(&mut self, history: &str, snapshot: &Snapshot) -> Result<(), SnapshotError> {
+		if let Some(c) = history.chars().find(|c| !matches!(c, 'U' | 'L' | 'D' | 'R' | 'T')) {
+			return Err(SnapshotError::Invalid(c.to_string()));
+		}
+		let (size_x, size_y) = (self.size_x(), self.size_y());
+		let on_map = |(x, y): (usize, usize)| x < size_x && y < size_y;
+		let fits = snapshot.history == history_hash(history)
+			&& snapshot.tiles.len() == size_x
+			&& snapshot.tiles.iter().all(|row| row.len() == size_y)
+			&& on_map(snapshot.player)
+			&& snapshot.level_entries.iter().all(|&(tile, index)| on_map(tile) && index < history.len())
+			&& snapshot.attempts.iter().all(|attempt| {
+				attempt.level.0 < self.levels.len()
+					&& attempt.start < history.len()
+					&& attempt.end.is_none_or(|end| end < history.len())
+			});
+		if !fits || !self.input_history.is_empty() {
+			return Err(SnapshotError::Mismatch);
+		}
+
+		self.tiles.clone_from(&snapshot.tiles);
+		self.snowballs.clone_from(&snapshot.snowballs);
+		self.player = snapshot.player;
+		self.level_entries.clone_from(&snapshot.level_entries);
+		self.attempts.clone_from(&snapshot.attempts);
+		self.input_history.push_str(history);
+		for action in history.chars() {
+			self.advance_in_tree(action);
+		}
+		return Ok(());
+	}
+}
+
+/// A FNV-1a of the history, to tell a snapshot was taken after it.
+fn history_hash(history: &str) -> u64 {
+	let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+	for byte in history.bytes() {
+		hash = (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3);
+	}
+	return hash;
+}
+
+fn tile_char(tile: Tile) -> char {
+	match tile {
+		Tile::Empty => '-',
+		Tile::Dirt => '.',
+		Tile::Snow => ',',
+		Tile::Hedge => '#',
+		Tile::Tree => 't',
+		Tile::Obstacle => 'o',
+		Tile::Ice => 'i',
+		Tile::Portal(number) => char::from(b'0' + number),
+	}
+}
+
+fn char_tile(c: char) -> Option<Tile> {
+	match c {
+		'-' => Some(Tile::Empty),
+		'.' => Some(Tile::Dirt),
+		',' => Some(Tile::Snow),
+		'#' => Some(Tile::Hedge),
+		't' => Some(Tile::Tree),
+		'o' => Some(Tile::Obstacle),
+		'i' => Some(Tile::Ice),
+		'1'..='9' => Some(Tile::Portal(c as u8 - b'0')),
+		_ => None,
+	}
+}
+
+/// The stacked balls are `a`, `b` and `c`, in the order of `SnowBall`.
+fn snowball_char(snowball: Option<SnowBall>) -> char {
+	match snowball {
+		None => '_',
+		Some(SnowBall::Small) => 's',
+		Some(SnowBall::Medium) => 'm',
+		Some(SnowBall::Large) => 'l',
+		Some(SnowBall::SmallOnLarge) => 'a',
+		Some(SnowBall::SmallOnMedium) => 'b',
+		Some(SnowBall::MediumOnLarge) => 'c',
+		Some(SnowBall::Snowman) => '*',
+	}
+}
+
+fn char_snowball(c: char) -> Option<Option<SnowBall>> {
+	match c {
+		'_' => Some(None),
+		's' => Some(Some(SnowBall::Small)),
+		'm' => Some(Some(SnowBall::Medium)),
+		'l' => Some(Some(SnowBall::Large)),
+		'a' => Some(Some(SnowBall::SmallOnLarge)),
+		'b' => Some(Some(SnowBall::SmallOnMedium)),
+		'c' => Some(Some(SnowBall::MediumOnLarge)),
+		'*' => Some(Some(SnowBall::Snowman)),
+		_ => None,
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	const MAP: &str = "P    \n s.  \n m.  \n l.  \n";
+
+	/// More actions than between two checkpoints, with entries into the level
+	/// and a reset.
+	fn history() -> String {
+		"RL".repeat(600) + "DRDUURDLTRLD"
+	}
+
+	#[test]
+	fn serialized_snapshot_is_parsed_back() {
+		let mut game = Game::from_map_str(MAP).unwrap();
+		game.apply_history(&history()).unwrap();
+		let snapshot = game.snapshot();
+		assert_eq!(Snapshot::parse(snapshot.serialize().lines()), Ok(snapshot.clone()));
+		let text = snapshot.serialize();
+		assert!(Snapshot::parse(text.lines().skip(1)).is_err());
+	}
+
+	#[test]
+	fn snapshot_load_matches_a_replay_even_after_rewinds() {
+		let mut replayed = Game::from_map_str(MAP).unwrap();
+		replayed.apply_history(&history()).unwrap();
+		assert_eq!(replayed.get_history(), history());
+		let snapshot = Snapshot::parse(replayed.snapshot().serialize().lines()).unwrap();
+		let mut loaded = Game::from_map_str(MAP).unwrap();
+		loaded.apply_snapshot(&history(), &snapshot).unwrap();
+
+		// Back beyond the checkpoint taken after 1024 actions.
+		for _ in 0..200 {
+			assert_eq!(loaded.snapshot(), replayed.snapshot());
+			assert_eq!(loaded.get_history(), replayed.get_history());
+			assert!(loaded.rewind());
+			assert!(replayed.rewind());
+		}
+		assert_eq!(loaded.snapshot(), replayed.snapshot());
+	}
+
+	#[test]
+	fn snapshot_of_another_history_is_refused() {
+		let mut game = Game::from_map_str(MAP).unwrap();
+		game.apply_history("DR").unwrap();
+		let snapshot = game.snapshot();
+		let mut other = Game::from_map_str(MAP).unwrap();
+		assert_eq!(other.apply_snapshot("D", &snapshot), Err(SnapshotError::Mismatch));
+		// As long, and as valid on the map.
+		assert_eq!(other.apply_snapshot("RL", &snapshot), Err(SnapshotError::Mismatch));
+		assert_eq!(other.get_history(), "");
+		assert_eq!(other.apply_snapshot("DX", &snapshot), Err(SnapshotError::Invalid("X".to_owned())));
+	}
+}
@@ -0,0 +1,490 @@
+//!
+//! Search for a sequence of moves that completes the player's current level.
+//!
+//! The search is a Dijkstra over the pushes of snowballs: between two pushes the
+//! player takes the shortest walk, and the cost of a push is that walk plus one.
+//! So the solutions found are the shortest ones in number of moves. The pushes are
+//! computed with the very rules of `Game::step`, on a scratch copy of the game.
+//!
+
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, HashSet, VecDeque};
+use std::time::{Duration, Instant};
+
+use super::*;
+
+/// Bounds on the work done by the solver.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Limits {
+	/// Maximum number of states visited, a state being the level right after a push.
+	pub max_nodes: usize,
+	/// Maximum duration of the search, unbounded if `None`.
+	pub max_duration: Option<Duration>,
+}
+
+impl Default for Limits {
+	fn default() -> Self {
+		Limits {
+			max_nodes: 2_000_000,
+			max_duration: Some(Duration::from_secs(10)),
+		}
+	}
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Solution {
+	/// The moves in the `U/L/D/R` alphabet of the input history.
+	pub moves: String,
+	/// How many states were visited to find the solution.
+	pub explored_nodes: usize,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SolveError {
+	/// The player is not inside a level.
+	NotInLevel,
+	/// Every reachable state was explored, none is a solution.
+	Unsolvable,
+	NodeLimitReached,
+	TimeLimitReached,
+}
+
+impl std::fmt::Display for SolveError {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			SolveError::NotInLevel => write!(f, "the player is not in a level"),
+			SolveError::Unsolvable => write!(f, "the level cannot be solved from here"),
+			SolveError::NodeLimitReached => write!(f, "the search explored too many states"),
+			SolveError::TimeLimitReached => write!(f, "the search took too long"),
+		}
+	}
+}
+
+impl std::error::Error for SolveError {}
+
+const DIRECTIONS: [Direction; 4] = [
+	Direction::Up,
+	Direction::Left,
+	Direction::Down,
+	Direction::Right,
+];
+
+impl Game {
+	/// Find the shortest sequence of moves which turns every snowball of the
+	/// player's current level into a snowman. The player may walk around the
+	/// level, but the snowballs outside of it are left where they are.
+	pub fn solve_current_level(&self, limits: &Limits) -> Result<Solution, SolveError> {
+		let level = LevelState::new(self).ok_or(SolveError::NotInLevel)?;
+		let start = Instant::now();
+		let mut work = self.clone();
+
+		let root = level.encode(&work);
+		if level.is_solved(&root) {
+			return Ok(Solution {
+				moves: String::new(),
+				explored_nodes: 1,
+			});
+		}
+		if level.ball_count(&root) % 3 != 0 {
+			return Err(SolveError::Unsolvable);
+		}
+
+		// The lowest known cost of each state, and the node reaching it with that cost.
+		let mut best = HashMap::<Box<[u8]>, (usize, usize)>::new();
+		let mut nodes = vec![Node {
+			key: root.clone(),
+			cost: 0,
+			parent: None,
+			moves: Box::from(""),
+		}];
+		let mut queue = BinaryHeap::from([(Reverse(0), 0)]);
+		best.insert(root, (0, 0));
+
+		while let Some((Reverse(cost), node)) = queue.pop() {
+			if best[&nodes[node].key].1 != node {
+				continue; // A cheaper path to that state was found after this one.
+			}
+			if level.is_solved(&nodes[node].key) {
+				return Ok(Solution {
+					moves: backtrack(&nodes, node),
+					explored_nodes: nodes.len(),
+				});
+			}
+			if nodes.len() > limits.max_nodes {
+				return Err(SolveError::NodeLimitReached);
+			}
+			if limits.max_duration.is_some_and(|d| start.elapsed() > d) {
+				return Err(SolveError::TimeLimitReached);
+			}
+
+			level.decode(&nodes[node].key, &mut work);
+			if !work.level_deadlocks(level.cells()).is_empty() {
+				continue;
+			}
+			for (walk, tile, dir) in level.pushes(&mut work) {
+				work.player = tile;
+				let Some(map_diff) = work.step(dir) else {
+					continue;
+				};
+				work.apply_update(&map_diff.new);
+				let next = level.encode(&work);
+				work.apply_update(&map_diff.old);
+
+				let next_cost = cost + walk.len() + 1;
+				if best.get(&next).is_some_and(|&(c, _)| c <= next_cost) {
+					continue;
+				}
+				let next_node = nodes.len();
+				let mut moves = walk;
+				moves.push(dir.into());
+				nodes.push(Node {
+					key: next.clone(),
+					cost: next_cost,
+					parent: Some(node),
+					moves: moves.into_boxed_str(),
+				});
+				best.insert(next, (next_cost, next_node));
+				queue.push((Reverse(next_cost), next_node));
+			}
+		}
+		return Err(SolveError::Unsolvable);
+	}
+}
+
+struct Node {
+	key: Box<[u8]>,
+	cost: usize,
+	parent: Option<usize>,
+	/// The walk from the parent's state and the push leading to this state.
+	moves: Box<str>,
+}
+
+/// Rebuild the moves leading from the root to the node `last`.
+fn backtrack(nodes: &[Node], last: usize) -> String {
+	let mut segments = Vec::new();
+	let mut node = last;
+	while let Some(parent) = nodes[node].parent {
+		segments.push(&*nodes[node].moves);
+		node = parent;
+	}
+	debug_assert_eq!(nodes[last].cost, segments.iter().map(|m| m.len()).sum::<usize>());
+	return segments.into_iter().rev().collect();
+}
+
+/// The tiles of a level, to encode its state in a compact key, and the tiles
+/// around it where the player can walk.
+/// A key is the player's index in `tiles` on two bytes followed by one
+/// byte per cell: the snowball's code and a bit for the snow.
+struct LevelState {
+	/// The level's cells, then the tiles outside of it next to them where the
+	/// player can stand, portal exits included.
+	tiles: Vec<(usize, usize)>,
+	/// How many of `tiles` are the level's cells.
+	cell_count: usize,
+	/// Index of each tile in `tiles`.
+	index: HashMap<(usize, usize), u16>,
+	/// The indices of the tiles reached by one move from each tile, through the
+	/// portals, in the order of `DIRECTIONS`.
+	neighbours: Vec<[Option<usize>; 4]>,
+	/// The tiles of `tiles` outside of the level that each one reaches by a walk
+	/// around the level, with the moves of the shortest such walk.
+	around: Vec<Vec<(usize, Box<str>)>>,
+}
+
+const SNOW_BIT: u8 = 0x10;
+
+impl LevelState {
+	fn new(game: &Game) -> Option<LevelState> {
+		let mut tiles = game.current_level()?.cells.clone();
+		let cell_count = tiles.len();
+		let is_cell: HashSet<(usize, usize)> = tiles.iter().copied().collect();
+		// The snowballs outside of the level belong to other levels, and are
+		// left where they are.
+		let outside_step = |(x, y): (usize, usize), dir: Direction| {
+			let (nx, ny) = game.try_step(x, y, dir)?;
+			if is_cell.contains(&(nx, ny)) || game.tiles[nx][ny].blocks() || game.snowballs[nx][ny].is_some() {
+				return None;
+			}
+			return Some(game.portals.exit((nx, ny)).unwrap_or((nx, ny)));
+		};
+		for i in 0..cell_count {
+			for dir in DIRECTIONS {
+				if let Some(next) = outside_step(tiles[i], dir)
+					&& !tiles.contains(&next)
+				{
+					tiles.push(next);
+				}
+			}
+		}
+		if tiles.len() > u16::MAX as usize {
+			return None;
+		}
+		let index: HashMap<(usize, usize), u16> = tiles
+			.iter()
+			.enumerate()
+			.map(|(i, &tile)| (tile, i as u16))
+			.collect();
+		let neighbours = tiles
+			.iter()
+			.map(|&(x, y)| {
+				DIRECTIONS.map(|dir| {
+					let next = game.try_step(x, y, dir)?;
+					let next = match is_cell.contains(&next) {
+						true => next,
+						false => outside_step((x, y), dir)?,
+					};
+					index.get(&next).map(|&i| i as usize)
+				})
+			})
+			.collect();
+		// A breadth-first search from each tile around the level, which does
+		// not go through the level.
+		let mut around = vec![Vec::new(); tiles.len()];
+		for (from, walks) in around.iter_mut().enumerate().skip(cell_count) {
+			let mut came_from = HashMap::from([(tiles[from], None)]);
+			let mut to_explore = VecDeque::from([tiles[from]]);
+			while let Some(tile) = to_explore.pop_front() {
+				for dir in DIRECTIONS {
+					if let Some(next) = outside_step(tile, dir)
+						&& !came_from.contains_key(&next)
+					{
+						came_from.insert(next, Some((tile, dir)));
+						to_explore.push_back(next);
+						if let Some(&to) = index.get(&next) {
+							walks.push((to as usize, walk_to(&came_from, next).into_boxed_str()));
+						}
+					}
+				}
+			}
+		}
+		return Some(LevelState {
+			tiles,
+			cell_count,
+			index,
+			neighbours,
+			around,
+		});
+	}
+
+	/// The level's cells, where the snowballs stay.
+	fn cells(&self) -> &[(usize, usize)] {
+		&self.tiles[..self.cell_count]
+	}
+
+	fn encode(&self, game: &Game) -> Box<[u8]> {
+		let mut key = Vec::with_capacity(2 + self.cell_count);
+		key.extend_from_slice(&self.index[&game.player].to_le_bytes());
+		for &(x, y) in self.cells() {
+			let snow = if game.tiles[x][y] == Tile::Snow { SNOW_BIT } else { 0 };
+			key.push(snow | snowball_code(game.snowballs[x][y]));
+		}
+		return key.into_boxed_slice();
+	}
+
+	fn decode(&self, key: &[u8], game: &mut Game) {
+		game.player = self.tiles[u16::from_le_bytes([key[0], key[1]]) as usize];
+		for (&(x, y), &byte) in self.cells().iter().zip(&key[2..]) {
+			// The ice never changes, only the snow becomes dirt.
+			if game.tiles[x][y] != Tile::Ice {
+				game.tiles[x][y] = if byte & SNOW_BIT != 0 { Tile::Snow } else { Tile::Dirt };
+			}
+			game.snowballs[x][y] = snowball_from_code(byte & !SNOW_BIT);
+		}
+	}
+
+	/// All the pushes the player can do from where they stand, without pushing
+	/// anything on the way: the shortest walk to a snowball, in the level or
+	/// around it, the tile where the walk ends and the push direction. The
+	/// player is moved back to their position.
+	fn pushes(&self, game: &mut Game) -> Vec<(String, (usize, usize), Direction)> {
+		let origin = game.player;
+		let mut pushes = Vec::new();
+		// The length of the shortest walk to each tile, and the tile it comes from.
+		let mut distance = vec![usize::MAX; self.tiles.len()];
+		let mut came_from = vec![None::<(usize, Step)>; self.tiles.len()];
+		let mut done = vec![false; self.tiles.len()];
+		let root = self.index[&origin] as usize;
+		let mut to_explore = BinaryHeap::from([(Reverse(0), root)]);
+		distance[root] = 0;
+
+		while let Some((Reverse(length), tile)) = to_explore.pop() {
+			if done[tile] {
+				continue;
+			}
+			done[tile] = true;
+			let mut reach = |next: usize, added: usize, step: Step| {
+				if length + added < distance[next] {
+					distance[next] = length + added;
+					came_from[next] = Some((tile, step));
+					to_explore.push((Reverse(length + added), next));
+				}
+			};
+			for (&neighbour, dir) in self.neighbours[tile].iter().zip(DIRECTIONS) {
+				let Some(next) = neighbour else {
+					continue;
+				};
+				let (nx, ny) = self.tiles[next];
+				if next < self.cell_count && game.snowballs[nx][ny].is_some() {
+					game.player = self.tiles[tile];
+					if game.step(dir).is_some() {
+						pushes.push((tile, dir));
+					}
+				} else {
+					reach(next, 1, Step::Move(dir));
+				}
+			}
+			for (i, (next, moves)) in self.around[tile].iter().enumerate() {
+				reach(*next, moves.len(), Step::Around(i));
+			}
+		}
+		game.player = origin;
+		// The walks to the tiles are final once they are explored.
+		return pushes
+			.into_iter()
+			.map(|(tile, dir)| (self.walk_to(&came_from, tile), self.tiles[tile], dir))
+			.collect();
+	}
+
+	/// The moves of the walk found by `pushes` to reach `tile`.
+	fn walk_to(&self, came_from: &[Option<(usize, Step)>], mut tile: usize) -> String {
+		let mut segments = Vec::new();
+		while let Some((previous, step)) = came_from[tile] {
+			segments.push(match step {
+				Step::Move(dir) => char::from(dir).to_string(),
+				Step::Around(i) => self.around[previous][i].1.to_string(),
+			});
+			tile = previous;
+		}
+		return segments.into_iter().rev().collect();
+	}
+
+	fn is_solved(&self, key: &[u8]) -> bool {
+		let snowman = snowball_code(Some(SnowBall::Snowman));
+		key[2..]
+			.iter()
+			.map(|byte| byte & !SNOW_BIT)
+			.all(|code| code == 0 || code == snowman)
+	}
+
+	/// The number of single balls, stacked or not.
+	fn ball_count(&self, key: &[u8]) -> usize {
+		key[2..]
+			.iter()
+			.map(|byte| snowball_from_code(byte & !SNOW_BIT).map_or(0, SnowBall::ball_count))
+			.sum()
+	}
+}
+
+/// How the walk found by `LevelState::pushes` goes from a tile to the next.
+#[derive(Clone, Copy)]
+enum Step {
+	Move(Direction),
+	/// The walk of that index in the tile's `around`.
+	Around(usize),
+}
+
+/// The tile each tile was reached from in a breadth-first search, and how.
+type CameFrom = HashMap<(usize, usize), Option<((usize, usize), Direction)>>;
+
+/// The moves of a walk found by a breadth-first search to reach `tile`.
+fn walk_to(came_from: &CameFrom, mut tile: (usize, usize)) -> String {
+	let mut moves = Vec::new();
+	while let Some((previous, dir)) = came_from[&tile] {
+		moves.push(char::from(dir));
+		tile = previous;
+	}
+	return moves.into_iter().rev().collect();
+}
+
+fn snowball_code(snowball: Option<SnowBall>) -> u8 {
+	match snowball {
+		None => 0,
+		Some(SnowBall::Small) => 1,
+		Some(SnowBall::Medium) => 2,
+		Some(SnowBall::Large) => 3,
+		Some(SnowBall::SmallOnMedium) => 4,
+		Some(SnowBall::SmallOnLarge) => 5,
+		Some(SnowBall::MediumOnLarge) => 6,
+		Some(SnowBall::Snowman) => 7,
+	}
+}
+
+fn snowball_from_code(code: u8) -> Option<SnowBall> {
+	match code {
+		1 => Some(SnowBall::Small),
+		2 => Some(SnowBall::Medium),
+		3 => Some(SnowBall::Large),
+		4 => Some(SnowBall::SmallOnMedium),
+		5 => Some(SnowBall::SmallOnLarge),
+		6 => Some(SnowBall::MediumOnLarge),
+		7 => Some(SnowBall::Snowman),
+		_ => None,
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn play(game: &mut Game, moves: &str) {
+		let history = format!("{}{moves}", game.get_history());
+		game.apply_history(moves).unwrap();
+		assert_eq!(game.get_history(), history, "a move is blocked");
+	}
+
+	/// Solve the current level, and check the solution does complete it.
+	fn assert_solves(mut game: Game) -> String {
+		let solution = game.solve_current_level(&Limits::default()).unwrap();
+		let level = LevelState::new(&game).unwrap();
+		play(&mut game, &solution.moves);
+		assert!(level.cells().iter().all(|&(x, y)| matches!(game.snowballs[x][y], None | Some(SnowBall::Snowman))));
+		return solution.moves;
+	}
+
+	#[test]
+	fn solves_a_level_from_inside() {
+		let mut game = Game::from_map_str("P\n...\n.m.\n.l.\n.s.\n").unwrap();
+		play(&mut game, "D");
+		assert_eq!(assert_solves(game).len(), 20);
+	}
+
+	#[test]
+	fn pushes_from_around_the_level() {
+		// The balls are stacked by pushing them down from above the level.
+		let mut game = Game::from_map_str("P    \n s.  \n m.  \n l.  \n").unwrap();
+		play(&mut game, "RRD");
+		assert_eq!(assert_solves(game).len(), 14);
+	}
+
+	#[test]
+	fn walks_through_portals_around_the_level() {
+		// The only way behind the ball is the portal on the other side.
+		let mut game = Game::from_map_str("#####\n1sz.1\n###P#\n").unwrap();
+		play(&mut game, "U");
+		assert_eq!(assert_solves(game), "RR");
+	}
+
+	#[test]
+	fn proves_a_level_unsolvable() {
+		let mut game = Game::from_map_str("P\n.ssl\n").unwrap();
+		play(&mut game, "D");
+		assert_eq!(game.solve_current_level(&Limits::default()), Err(SolveError::Unsolvable));
+	}
+
+	#[test]
+	fn follows_the_rules_of_the_level() {
+		// The ball grows on the snow, too big for the stack, unless the level says not to.
+		let mut game = Game::from_map_str("P\n.s,z\n").unwrap();
+		play(&mut game, "D");
+		assert_eq!(game.solve_current_level(&Limits::default()), Err(SolveError::Unsolvable));
+		let mut game = Game::from_map_str("P\n.s,z\n!level 2:3 no-growth\n").unwrap();
+		play(&mut game, "D");
+		assert_eq!(assert_solves(game), "RR");
+	}
+
+	#[test]
+	fn refuses_outside_a_level() {
+		let game = Game::from_map_str("P \n.sml\n").unwrap();
+		assert_eq!(game.solve_current_level(&Limits::default()), Err(SolveError::NotInLevel));
+	}
+}
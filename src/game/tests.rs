@@ -0,0 +1,470 @@
+//!
+//! The maps read at runtime, the moves of the player on the embedded map,
+//! their rewind, resets included, and redo, their replay from a history, the
+//! events they queue, the rules of the portals and the ice, those the map
+//! gives to some levels, the deadlocks found in a level, and where a reset
+//! puts the player back.
+//!
+
+use super::*;
+use deadlock::Deadlock;
+
+fn game(map: &str) -> Game {
+	Game::from_map_str(map).expect("The test map is valid.")
+}
+
+#[test]
+fn short_rows_are_padded_with_empty_tiles() {
+	let game = game("#.\n\nP,S\n");
+	assert_eq!((game.size_x(), game.size_y()), (3, 3));
+	assert_eq!(game.player, (2, 0));
+	assert_eq!(game.tiles[0], [Tile::Hedge, Tile::Dirt, Tile::Empty]);
+	assert_eq!(game.tiles[1], [Tile::Empty; 3]);
+	assert_eq!(game.tiles[2], [Tile::Empty, Tile::Snow, Tile::Snow]);
+	assert_eq!(game.snowballs[2][2], Some(SnowBall::Small));
+}
+
+#[test]
+fn maps_without_a_single_player_are_refused() {
+	assert!(matches!(Game::from_map_str("\n\n"), Err(MapError::Empty)));
+	assert!(matches!(Game::from_map_str("..\n"), Err(MapError::MissingPlayer)));
+	let error = Game::from_map_str("P.\n.P\n").unwrap_err();
+	assert_eq!(
+		error.to_string(),
+		"there cannot be two player's initial positions on the map, found at 1:1 and 2:2"
+	);
+	let missing = std::env::temp_dir().join("snowcrab-no-such-map.txt");
+	assert!(matches!(Game::from_map_file(missing), Err(MapError::Io(_))));
+}
+
+/// Where the player starts on the embedded map, below a level.
+const START: (usize, usize) = (23, 6);
+
+#[test]
+fn player_walks_on_the_floor_and_not_through_hedges() {
+	let mut game = Game::instanciate();
+	assert_eq!(game.player, START);
+	assert!(!game.process_player_input(Direction::Left));
+	assert!(game.process_player_input(Direction::Up));
+	assert!(game.process_player_input(Direction::Up));
+	assert_eq!(game.player, (21, 6));
+	assert_eq!(game.get_history(), "UU");
+}
+
+#[test]
+fn rewind_undoes_the_last_move() {
+	let mut game = Game::instanciate();
+	assert!(!game.rewind());
+	game.process_player_input(Direction::Up);
+	game.process_player_input(Direction::Up);
+	assert!(game.rewind());
+	assert_eq!(game.player, (22, 6));
+	assert_eq!(game.get_history(), "U");
+}
+
+#[test]
+fn history_is_replayed() {
+	let mut played = Game::instanciate();
+	for dir in [Direction::Up, Direction::Up, Direction::Left, Direction::Down] {
+		assert!(played.process_player_input(dir));
+	}
+	let mut replayed = Game::instanciate();
+	replayed.apply_history("UULD").unwrap();
+	assert_eq!(replayed.player, played.player);
+	assert_eq!(replayed.snowballs, played.snowballs);
+	assert_eq!(replayed.get_history(), "UULD");
+
+	assert_eq!(Game::instanciate().apply_history("UxD"), Err('x'));
+}
+
+#[test]
+fn reset_is_rewound_in_one_action() {
+	let mut game = game("P\n.s.ml\n");
+	for dir in [Direction::Down, Direction::Right, Direction::Right] {
+		assert!(game.process_player_input(dir));
+	}
+	assert_eq!(game.snowballs[1][3], Some(SnowBall::SmallOnMedium));
+	assert!(game.reset_current_level());
+	assert_eq!(game.snowballs[1][1], Some(SnowBall::Small));
+	assert_eq!(game.snowballs[1][3], Some(SnowBall::Medium));
+	assert!(game.rewind());
+	assert_eq!(game.player, (1, 2));
+	assert_eq!(game.snowballs[1][1], None);
+	assert_eq!(game.snowballs[1][3], Some(SnowBall::SmallOnMedium));
+	assert_eq!(game.get_history(), "DRR");
+}
+
+#[test]
+fn redo_plays_the_rewound_actions_again() {
+	let mut game = game("P  \n");
+	assert!(!game.redo());
+	assert!(game.process_player_input(Direction::Right));
+	assert!(game.process_player_input(Direction::Right));
+	assert!(game.rewind());
+	assert!(game.rewind());
+	assert_eq!(game.get_redo(), "RR");
+	// Playing the next action to redo keeps the rest.
+	assert!(game.process_player_input(Direction::Right));
+	assert_eq!(game.get_redo(), "R");
+	assert!(game.redo());
+	assert_eq!(game.player, (0, 2));
+	assert!(!game.redo());
+}
+
+#[test]
+fn another_action_forgets_the_redo() {
+	let mut game = game("P  \n   \n");
+	assert!(game.process_player_input(Direction::Right));
+	assert!(game.rewind());
+	// A blocked move is no action.
+	assert!(!game.process_player_input(Direction::Left));
+	assert_eq!(game.get_redo(), "R");
+	assert!(game.process_player_input(Direction::Down));
+	assert_eq!(game.get_redo(), "");
+	assert!(!game.redo());
+}
+
+#[test]
+fn rewinds_go_back_further_than_the_recent_undos() {
+	let mut game = game("P.\n");
+	for i in 0..5000 {
+		let dir = if i % 2 == 0 { Direction::Right } else { Direction::Left };
+		assert!(game.process_player_input(dir));
+	}
+	for len in (0..5000).rev() {
+		assert!(game.rewind());
+		assert_eq!(game.player, (0, len % 2));
+		assert_eq!(game.rewind_depth(), len);
+	}
+	assert!(!game.rewind());
+}
+
+#[test]
+fn blocked_moves_tell_why() {
+	let mut game = game("P#\ns#\n");
+	game.take_events();
+	for dir in [Direction::Up, Direction::Right, Direction::Down] {
+		assert!(!game.process_player_input(dir));
+	}
+	let reasons: Vec<BlockReason> = game
+		.take_events()
+		.into_iter()
+		.map(|event| match event {
+			GameEvent::MoveBlocked { reason, .. } => reason,
+			other => panic!("{other:?} is not a blocked move"),
+		})
+		.collect();
+	assert_eq!(
+		reasons,
+		[BlockReason::OutsideMap, BlockReason::Obstacle(Tile::Hedge), BlockReason::StuckSnowball]
+	);
+}
+
+#[test]
+fn snowman_built_is_an_event() {
+	let mut game = game("P    \n s.  \n m.  \n l.  \n");
+	for c in "DRDUURDRDLULD".chars() {
+		assert!(game.process_player_input(c.try_into().unwrap()));
+	}
+	assert!(game.take_events().contains(&GameEvent::SnowmanBuilt { x: 3, y: 1 }));
+}
+
+#[test]
+fn nothing_to_rewind_or_redo_is_an_event() {
+	let mut game = game("P \n");
+	game.take_events();
+	assert!(!game.rewind());
+	assert!(!game.redo());
+	assert_eq!(game.take_events(), [GameEvent::RewindExhausted, GameEvent::NothingToRedo]);
+}
+
+#[test]
+fn history_not_matching_the_map_is_an_event() {
+	let mut game = game("P \n");
+	game.take_events();
+	game.apply_history("RRL").unwrap();
+	assert_eq!(game.get_history(), "R");
+	assert!(game.take_events().contains(&GameEvent::SaveDesync { index: 1 }));
+}
+
+/// The deadlocks of the level below the player, who starts at the top left.
+fn deadlocks(map: &str) -> Vec<Deadlock> {
+	let mut game = game(map);
+	assert!(game.process_player_input(Direction::Down));
+	return game.current_level_deadlocks();
+}
+
+#[test]
+fn level_with_a_snowman_to_build_has_no_deadlock() {
+	assert_eq!(deadlocks("P\n.sml.\n"), []);
+}
+
+#[test]
+fn frozen_ball_is_a_small_one_which_cannot_move() {
+	// Against the edge of the map, with nothing to hold it above.
+	assert_eq!(deadlocks("P\n.lms\n"), [Deadlock::FrozenBall { at: (1, 3) }]);
+	// A large ball stuck there is still the base of a snowman.
+	assert_eq!(deadlocks("P\n.sml\n"), []);
+}
+
+#[test]
+fn isolated_balls_cannot_meet_enough_others() {
+	// The hedges keep the balls of the second row apart, they cannot be pushed up.
+	let expected = [
+		Deadlock::IsolatedBalls { balls: vec![(1, 1)] },
+		Deadlock::IsolatedBalls {
+			balls: vec![(1, 5), (1, 6)],
+		},
+	];
+	assert_eq!(deadlocks("P,,,......\n.s.#.ss.#.\n"), expected);
+	assert_eq!(deadlocks("P,,,......\n.s...ss...\n"), []);
+}
+
+#[test]
+fn ball_count_is_a_multiple_of_three() {
+	assert_eq!(deadlocks("P\n.ss..\n"), [Deadlock::BallCount { count: 2 }]);
+	// A stack counts as two balls.
+	assert_eq!(deadlocks("P\n.sx,,.\n"), []);
+}
+
+#[test]
+fn balls_too_big_cannot_all_be_bases() {
+	assert_eq!(deadlocks("P\n.sll.\n"), [Deadlock::BallsTooBig]);
+	assert_eq!(deadlocks("P\n.mml.\n"), [Deadlock::BallsTooBig]);
+	assert_eq!(deadlocks("P\n.sml.\n"), []);
+}
+
+#[test]
+fn not_enough_snow_to_grow_the_balls() {
+	assert_eq!(deadlocks("P\n.sss.\n"), [Deadlock::NotEnoughSnow { missing: 3 }]);
+	assert_eq!(deadlocks("P\n.sss,,,\n"), []);
+}
+
+/// Play the moves, which must not be blocked.
+fn play(game: &mut Game, moves: &str) {
+	for c in moves.chars() {
+		assert!(game.process_player_input(c.try_into().unwrap()), "{c} is blocked");
+	}
+}
+
+/// A level of two rows under an empty one, with a small ball.
+const RESET_MAP: &str = "P   \n.s..\n....\n";
+
+#[test]
+fn reset_puts_the_player_back_at_the_first_entry() {
+	let mut game = game(RESET_MAP);
+	play(&mut game, "DURRDL");
+	assert_eq!(game.snowballs[1][0], Some(SnowBall::Small));
+	assert!(game.reset_current_level());
+	assert_eq!(game.player, (1, 0));
+	assert_eq!(game.snowballs[1][1], Some(SnowBall::Small));
+}
+
+#[test]
+fn reset_moves_the_player_off_an_initial_snowball() {
+	// The player came in by pushing the ball, which comes back at the entry.
+	let mut game = game(RESET_MAP);
+	play(&mut game, "RD");
+	assert_eq!(game.player, (1, 1));
+	assert!(game.reset_current_level());
+	assert_eq!(game.player, (2, 1));
+	assert_eq!(game.snowballs[1][1], Some(SnowBall::Small));
+}
+
+#[test]
+fn reset_is_rejected_if_the_level_is_full_of_snowballs() {
+	let mut game = game(" \nPsm\n");
+	play(&mut game, "R");
+	assert_eq!(game.snowballs[1][2], Some(SnowBall::SmallOnMedium));
+	game.take_events();
+	assert!(!game.reset_current_level());
+	assert_eq!(game.take_events(), [GameEvent::ResetRejected]);
+	assert_eq!(game.player, (1, 1));
+}
+
+#[test]
+fn rewound_entries_are_forgotten() {
+	let mut game = game(RESET_MAP);
+	play(&mut game, "DU");
+	assert!(game.rewind());
+	assert!(game.rewind());
+	play(&mut game, "RRDL");
+	assert!(game.reset_current_level());
+	assert_eq!(game.player, (1, 2));
+}
+
+#[test]
+fn portal_takes_the_player_to_the_other_end() {
+	let mut game = game("P1 \n  1\n");
+	game.take_events();
+	assert!(game.process_player_input(Direction::Right));
+	assert_eq!(game.player, (1, 2));
+	assert_eq!(
+		game.take_events(),
+		[GameEvent::Teleported {
+			from: (0, 1),
+			to: (1, 2)
+		}]
+	);
+}
+
+#[test]
+fn portal_works_both_ways() {
+	let mut game = game("P1 \n  1\n");
+	game.process_player_input(Direction::Right);
+	game.process_player_input(Direction::Left);
+	assert_eq!(game.player, (1, 1));
+	game.process_player_input(Direction::Right);
+	assert_eq!(game.player, (0, 1));
+}
+
+#[test]
+fn portal_is_rewound_in_one_action() {
+	let mut game = game("P1 \n  1\n");
+	game.process_player_input(Direction::Right);
+	assert!(game.rewind());
+	assert_eq!(game.player, (0, 0));
+}
+
+#[test]
+fn portal_without_other_end_is_a_floor() {
+	for map in ["P3 \n", "P3 3\n   3\n"] {
+		let mut game = game(map);
+		game.take_events();
+		assert!(game.process_player_input(Direction::Right));
+		assert_eq!(game.player, (0, 1));
+		assert_eq!(game.take_events(), []);
+	}
+}
+
+#[test]
+fn snowball_cannot_be_pushed_onto_a_portal() {
+	let mut game = game("P.s1\n   1\n");
+	game.process_player_input(Direction::Right);
+	assert!(game.step(Direction::Right).is_none());
+	assert_eq!(game.block_reason(Direction::Right), BlockReason::StuckSnowball);
+}
+
+#[test]
+fn level_can_be_entered_from_a_portal() {
+	let game = game("P1 \n  #\n1.s\n");
+	let level = game.levels().iter().next().unwrap();
+	assert_eq!(level.doors, [(2, 1)]);
+}
+
+#[test]
+fn snowball_slides_on_ice() {
+	let mut game = game("Psiiii.\n");
+	assert!(game.process_player_input(Direction::Right));
+	assert_eq!(game.player, (0, 1));
+	assert_eq!(game.snowballs[0][1], None);
+	assert_eq!(game.snowballs[0][5], Some(SnowBall::Small));
+	assert_eq!(game.snowballs[0][6], None);
+}
+
+#[test]
+fn snowball_slides_until_another_snowball() {
+	let mut game = game("Psiiii\n");
+	game.snowballs[0][5] = Some(SnowBall::Large);
+	game.process_player_input(Direction::Right);
+	assert_eq!(game.snowballs[0][4], Some(SnowBall::Small));
+	assert_eq!(game.snowballs[0][5], Some(SnowBall::Large));
+}
+
+#[test]
+fn snowball_stacks_on_a_snowball_on_ice() {
+	let mut game = game("Psiii\n");
+	game.snowballs[0][2] = Some(SnowBall::Large);
+	game.process_player_input(Direction::Right);
+	assert_eq!(game.snowballs[0][1], None);
+	assert_eq!(game.snowballs[0][2], Some(SnowBall::SmallOnLarge));
+}
+
+#[test]
+fn top_snowball_slides_off_a_stack() {
+	let mut game = game("Pxiii\n");
+	game.process_player_input(Direction::Right);
+	assert_eq!(game.player, (0, 0));
+	assert_eq!(game.snowballs[0][1], Some(SnowBall::Medium));
+	assert_eq!(game.snowballs[0][4], Some(SnowBall::Small));
+}
+
+#[test]
+fn snowball_does_not_slide_from_ice_onto_snow() {
+	let mut game = game("Psi,\n");
+	game.process_player_input(Direction::Right);
+	assert_eq!(game.snowballs[0][2], Some(SnowBall::Small));
+	assert_eq!(game.tiles[0][3], Tile::Snow);
+	game.process_player_input(Direction::Right);
+	assert_eq!(game.snowballs[0][3], Some(SnowBall::Medium));
+	assert_eq!(game.tiles[0][3], Tile::Dirt);
+}
+
+#[test]
+fn slide_is_rewound_in_one_action() {
+	let mut game = game("Psiiii.\n");
+	game.process_player_input(Direction::Right);
+	assert!(game.rewind());
+	assert_eq!(game.player, (0, 0));
+	assert_eq!(game.snowballs[0][1], Some(SnowBall::Small));
+	assert_eq!(game.snowballs[0][5], None);
+}
+
+#[cfg(feature = "solver")]
+#[test]
+fn solver_plays_by_the_ice() {
+	// The ball slides next to the stack, then builds the snowman.
+	let mut game = game("P\n.siiiz\n");
+	game.process_player_input(Direction::Down);
+	let solution = game.solve_current_level(&solver::Limits::default()).unwrap();
+	assert_eq!(solution.moves, "RRRR");
+	for c in solution.moves.chars() {
+		assert!(game.process_player_input(c.try_into().unwrap()));
+	}
+	assert_eq!(game.snowballs[1][5], Some(SnowBall::Snowman));
+	assert_eq!(game.tiles[1][2], Tile::Ice);
+}
+
+#[test]
+fn no_growth_keeps_the_balls_and_the_snow() {
+	let mut game = game("P   \n.s,z\n!level 2:4 no-growth\n");
+	game.process_player_input(Direction::Down);
+	assert!(game.process_player_input(Direction::Right));
+	assert_eq!(game.snowballs[1][2], Some(SnowBall::Small));
+	assert_eq!(game.tiles[1][2], Tile::Snow);
+	assert_eq!(game.current_level().unwrap().title(), "Level 1 (no-growth)");
+}
+
+#[test]
+fn no_reset_rejects_the_resets() {
+	let mut game = game("P   \n.s,z\n!level 2:1 no-reset no-growth\n");
+	game.process_player_input(Direction::Down);
+	game.process_player_input(Direction::Right);
+	game.take_events();
+	assert!(!game.reset_current_level());
+	assert_eq!(game.take_events(), [GameEvent::ResetRejected]);
+	assert!(game.rewind());
+}
+
+#[test]
+fn rules_are_part_of_the_map() {
+	let plain = game("P   \n.s,z\n");
+	let ruled = game("P   \n.s,z\n!level 2:1 no-growth\n");
+	assert_ne!(ruled.map_hash(), plain.map_hash());
+	assert!(plain.levels().iter().all(|level| level.rules.is_empty()));
+}
+
+#[test]
+fn invalid_rules_are_refused() {
+	for (map, line) in [
+		("P   \n.s,z\n!level 2:1 no-snow\n", 3),
+		("P   \n.s,z\n!level 1:1 no-reset\n", 3),
+		("P   \n.s,z\n!level 2:1\n", 3),
+		("P   \n.s,z\n!level 2:1 no-reset\n\n!lvl 2:2 no-reset\n", 5),
+	] {
+		match Game::from_map_str(map) {
+			Err(MapError::InvalidRule { line: l, .. }) => assert_eq!(l, line, "{map:?}"),
+			other => panic!("{map:?} gives {other:?}"),
+		}
+	}
+}
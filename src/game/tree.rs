@@ -0,0 +1,396 @@
+//!
+//! The history of the actions as a tree. Playing a different action after
+//! rewinding starts a new branch instead of forgetting the previous line of
+//! play. Among the children of a node, the most recently visited one comes
+//! first; it's the one `Game::redo` goes to.
+//!
+
+use std::collections::BTreeMap;
+
+use super::*;
+
+/// A node of the history tree, that is the state of the game after the
+/// actions on the path from the root.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub struct NodeId(u32);
+
+impl NodeId {
+	/// The start of the game, before any action.
+	pub const ROOT: NodeId = NodeId(0);
+}
+
+const NONE: u32 = u32::MAX;
+
+/// 16 bytes per action.
+#[derive(Clone, Debug)]
+struct Node {
+	parent: u32,
+	first_child: u32,
+	next_sibling: u32,
+	action: u8,
+}
+
+#[derive(Clone, Debug)]
+pub struct HistoryTree {
+	nodes: Vec<Node>,
+	current: NodeId,
+	bookmarks: BTreeMap<String, NodeId>,
+}
+
+impl HistoryTree {
+	pub(super) fn new() -> HistoryTree {
+		HistoryTree {
+			nodes: vec![Node {
+				parent: NONE,
+				first_child: NONE,
+				next_sibling: NONE,
+				action: 0,
+			}],
+			current: NodeId::ROOT,
+			bookmarks: BTreeMap::new(),
+		}
+	}
+
+	/// The node of the game's current state.
+	pub fn current(&self) -> NodeId {
+		self.current
+	}
+
+	pub fn parent(&self, node: NodeId) -> Option<NodeId> {
+		let parent = self.nodes[node.0 as usize].parent;
+		(parent != NONE).then_some(NodeId(parent))
+	}
+
+	/// The action leading to the node, `None` for the root.
+	pub fn action(&self, node: NodeId) -> Option<char> {
+		(node != NodeId::ROOT).then(|| self.nodes[node.0 as usize].action.into())
+	}
+
+	/// The branches starting at the node, the most recently visited first.
+	pub fn children(&self, node: NodeId) -> Vec<NodeId> {
+		let mut children = Vec::new();
+		let mut child = self.nodes[node.0 as usize].first_child;
+		while child != NONE {
+			children.push(NodeId(child));
+			child = self.nodes[child as usize].next_sibling;
+		}
+		return children;
+	}
+
+	pub fn child(&self, node: NodeId, action: char) -> Option<NodeId> {
+		self.children(node)
+			.into_iter()
+			.find(|&child| self.action(child) == Some(action))
+	}
+
+	/// The actions from the root to the node.
+	pub fn path(&self, node: NodeId) -> String {
+		let mut path = Vec::new();
+		let mut node = node;
+		while let Some(parent) = self.parent(node) {
+			path.push(self.action(node).expect("not the root"));
+			node = parent;
+		}
+		return path.into_iter().rev().collect();
+	}
+
+	/// The actions `Game::redo` would replay from the node, following the
+	/// most recently visited child each time.
+	pub fn redo_line(&self, node: NodeId) -> String {
+		let mut line = String::new();
+		let mut node = node;
+		while let Some(&child) = self.children(node).first() {
+			line.push(self.action(child).expect("not the root"));
+			node = child;
+		}
+		return line;
+	}
+
+	pub fn len(&self) -> usize {
+		self.nodes.len()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.nodes.len() == 1
+	}
+
+	pub fn bookmarks(&self) -> impl Iterator<Item = (&str, NodeId)> {
+		self.bookmarks.iter().map(|(name, &node)| (name.as_str(), node))
+	}
+
+	pub fn bookmark(&self, name: &str) -> Option<NodeId> {
+		self.bookmarks.get(name).copied()
+	}
+
+	/// The child of `node` for `action`, created if needed, and made the
+	/// most recently visited one.
+	fn visit_child(&mut self, node: NodeId, action: char) -> NodeId {
+		let child = match self.child(node, action) {
+			Some(child) => child,
+			None => self.add_child(node, action),
+		};
+		self.make_most_recent(child);
+		return child;
+	}
+
+	/// Create a child as the least recently visited one.
+	fn add_child(&mut self, node: NodeId, action: char) -> NodeId {
+		let id = u32::try_from(self.nodes.len()).expect("Less than 4 billion actions.");
+		self.nodes.push(Node {
+			parent: node.0,
+			first_child: NONE,
+			next_sibling: NONE,
+			action: action as u8,
+		});
+		match self.children(node).last() {
+			Some(last) => self.nodes[last.0 as usize].next_sibling = id,
+			None => self.nodes[node.0 as usize].first_child = id,
+		}
+		return NodeId(id);
+	}
+
+	fn make_most_recent(&mut self, child: NodeId) {
+		let parent = self.nodes[child.0 as usize].parent as usize;
+		let mut link = self.nodes[parent].first_child;
+		if link == child.0 {
+			return;
+		}
+		// Unlink the child, then put it first.
+		while self.nodes[link as usize].next_sibling != child.0 {
+			link = self.nodes[link as usize].next_sibling;
+		}
+		self.nodes[link as usize].next_sibling = self.nodes[child.0 as usize].next_sibling;
+		self.nodes[child.0 as usize].next_sibling = self.nodes[parent].first_child;
+		self.nodes[parent].first_child = child.0;
+	}
+
+	/// The nodes along `path` from the root, created as least recently visited
+	/// ones where missing.
+	fn insert_path(&mut self, path: &str) -> NodeId {
+		let mut node = NodeId::ROOT;
+		for action in path.chars() {
+			node = match self.child(node, action) {
+				Some(child) => child,
+				None => self.add_child(node, action),
+			};
+		}
+		return node;
+	}
+
+	/// The whole tree as text: the actions in depth-first order, with all
+	/// but the last child of a node enclosed in parentheses.
+	/// For instance `UL(D)(RR)T` is the line `ULT`, with branches `ULD` and `ULRR`.
+	pub fn serialize(&self) -> String {
+		enum Item {
+			Node(NodeId),
+			Branch(NodeId),
+			EndBranch,
+		}
+		let mut text = String::with_capacity(self.nodes.len());
+		let mut stack = vec![Item::Node(NodeId::ROOT)];
+		while let Some(item) = stack.pop() {
+			let node = match item {
+				Item::Node(node) => node,
+				Item::Branch(node) => {
+					text.push('(');
+					node
+				}
+				Item::EndBranch => {
+					text.push(')');
+					continue;
+				}
+			};
+			if let Some(action) = self.action(node) {
+				text.push(action);
+			}
+			let children = self.children(node);
+			if let Some((&last, others)) = children.split_last() {
+				stack.push(Item::Node(last));
+				for &child in others.iter().rev() {
+					stack.push(Item::EndBranch);
+					stack.push(Item::Branch(child));
+				}
+			}
+		}
+		return text;
+	}
+
+	/// Add the nodes of a tree given by `serialize`. The existing nodes stay
+	/// the most recently visited ones. Return the unexpected character, if any.
+	fn insert_serialized(&mut self, text: &str) -> Result<(), char> {
+		let mut branch_points = Vec::new();
+		let mut node = NodeId::ROOT;
+		for c in text.chars() {
+			match c {
+				'(' => branch_points.push(node),
+				')' => node = branch_points.pop().ok_or(c)?,
+				'U' | 'L' | 'D' | 'R' | 'T' => {
+					node = match self.child(node, c) {
+						Some(child) => child,
+						None => self.add_child(node, c),
+					};
+				}
+				_ => return Err(c),
+			}
+		}
+		return Ok(());
+	}
+}
+
+impl Game {
+	pub fn history_tree(&self) -> &HistoryTree {
+		&self.tree
+	}
+
+	/// Called when the player performs an action, with `Game::perform_new`.
+	pub(super) fn advance_in_tree(&mut self, action: char) {
+		self.tree.current = self.tree.visit_child(self.tree.current, action);
+	}
+
+	/// Called when the player rewinds one action.
+	pub(super) fn retreat_in_tree(&mut self) {
+		self.tree.current = self.tree.parent(self.tree.current).expect("not at the root");
+	}
+
+	/// Perform again the last rewound action, in the most recently visited branch.
+	pub fn redo(&mut self) -> bool {
+		let Some(&child) = self.tree.children(self.tree.current).first() else {
+			self.events.push(GameEvent::NothingToRedo);
+			return false;
+		};
+		let action = self.tree.action(child).expect("not the root");
+		if !self.perform_new(action) {
+			let index = self.input_history.len();
+			self.events.push(GameEvent::HistoryDesync { index });
+			return false;
+		}
+		return true;
+	}
+
+	/// Make a branch of the current node the one `redo` goes to.
+	pub fn select_branch(&mut self, child: NodeId) -> bool {
+		if self.tree.parent(child) != Some(self.tree.current) {
+			return false;
+		}
+		self.tree.make_most_recent(child);
+		return true;
+	}
+
+	/// Rewind and replay actions until the game is in the state of the node.
+	pub fn jump_to(&mut self, node: NodeId) -> bool {
+		let target = self.tree.path(node);
+		let common = self
+			.input_history
+			.bytes()
+			.zip(target.bytes())
+			.take_while(|(a, b)| a == b)
+			.count();
+		while self.input_history.len() > common {
+			self.rewind();
+		}
+		for action in target[common..].chars() {
+			if !self.perform_new(action) {
+				let index = self.input_history.len();
+				self.events.push(GameEvent::HistoryDesync { index });
+				return false;
+			}
+		}
+		return true;
+	}
+
+	/// Give a name to the current node. A bookmark with the same name is replaced.
+	pub fn set_bookmark(&mut self, name: &str) {
+		self.tree.bookmarks.insert(name.to_owned(), self.tree.current);
+	}
+
+	pub fn remove_bookmark(&mut self, name: &str) -> bool {
+		self.tree.bookmarks.remove(name).is_some()
+	}
+
+	pub fn jump_to_bookmark(&mut self, name: &str) -> bool {
+		match self.tree.bookmark(name) {
+			Some(node) => self.jump_to(node),
+			None => false,
+		}
+	}
+
+	/// The actions that can be redone, in the order they would be.
+	pub fn get_redo(&self) -> String {
+		self.tree.redo_line(self.tree.current)
+	}
+
+	/// Add a branch of actions after the current state and make it the one to redo.
+	pub fn set_redo(&mut self, redo: &str) -> Result<(), char> {
+		if let Some(c) = redo.chars().find(|c| !matches!(c, 'U' | 'L' | 'D' | 'R' | 'T')) {
+			return Err(c);
+		}
+		let mut node = self.tree.current;
+		for action in redo.chars() {
+			node = self.tree.visit_child(node, action);
+		}
+		Ok(())
+	}
+
+	/// Add the branches of a tree given by `HistoryTree::serialize`, without
+	/// changing the current state.
+	pub fn insert_history_tree(&mut self, text: &str) -> Result<(), char> {
+		self.tree.insert_serialized(text)
+	}
+
+	/// Bookmark the node at the end of `path`, creating the missing nodes.
+	pub fn insert_bookmark(&mut self, name: &str, path: &str) -> Result<(), char> {
+		if let Some(c) = path.chars().find(|c| !matches!(c, 'U' | 'L' | 'D' | 'R' | 'T')) {
+			return Err(c);
+		}
+		let node = self.tree.insert_path(path);
+		self.tree.bookmarks.insert(name.to_owned(), node);
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/// A game on an open field, where `actions` were played, `-` being a rewind.
+	fn played(actions: &str) -> Game {
+		let mut game = Game::from_map_str("P    \n     \n     \n").unwrap();
+		for c in actions.chars() {
+			match c {
+				'-' => assert!(game.rewind()),
+				_ => assert!(game.process_player_input(c.try_into().unwrap()), "{c} is blocked"),
+			}
+		}
+		game
+	}
+
+	#[test]
+	fn serialize_lists_the_branches_most_recent_first() {
+		let game = played("RR-D--DRD");
+		assert_eq!(game.history_tree().serialize(), "(DRD)R(D)R");
+		assert_eq!(game.get_history(), "DRD");
+	}
+
+	#[test]
+	fn serialized_tree_is_inserted_back() {
+		let game = played("RR-D--DRD-L---RDR");
+		let text = game.history_tree().serialize();
+
+		let mut empty = played("");
+		empty.insert_history_tree(&text).unwrap();
+		assert_eq!(empty.history_tree().serialize(), text);
+
+		// Like a save being loaded: the current line is played first.
+		let mut loaded = played("");
+		loaded.apply_history(game.get_history()).unwrap();
+		loaded.insert_history_tree(&text).unwrap();
+		assert_eq!(loaded.history_tree().serialize(), text);
+		assert_eq!(loaded.history_tree().len(), game.history_tree().len());
+	}
+
+	#[test]
+	fn insert_rejects_unknown_actions() {
+		let mut game = played("");
+		assert_eq!(game.insert_history_tree("R(D)X"), Err('X'));
+	}
+}
@@ -0,0 +1,432 @@
+//!
+//! Play in a piston window, drawn by `view`.
+//!
+
+// http://docs.piston.rs/piston_window/piston_window/
+use piston_window::*;
+
+use std::collections::VecDeque;
+use std::time::Duration;
+
+use snowcrab::deadlock::Deadlock;
+use snowcrab::hint::Hint;
+use snowcrab::replay::Replay;
+use snowcrab::save::autosave::Autosave;
+use snowcrab::{game, solver};
+
+use crate::{autosave_message, cli, event_message, hint_message, view};
+
+#[cfg(feature = "editor")]
+pub mod editor;
+
+const TITLE: &str = "A good snowcrab is hard to build.";
+/// Time between two moves when showing a solution, in seconds.
+const SOLUTION_STEP: f64 = 0.25;
+/// The replay speeds, in actions per second.
+const REPLAY_SPEEDS: [u32; 8] = [1, 2, 4, 8, 16, 32, 64, 128];
+const REPLAY_DEFAULT_SPEED: usize = 2;
+/// The limits of a whole hint, during which the window does not respond.
+const HINT_LIMITS: solver::Limits = solver::Limits {
+	max_nodes: 2_000_000,
+	max_duration: Some(Duration::from_secs(3)),
+};
+
+/// Play in a piston window until the player closes it. `message` is shown first.
+pub fn play(
+	game: &mut game::Game,
+	options: &cli::Options,
+	message: Option<String>,
+	autosave: Option<Autosave>,
+) {
+	let mut window = open_window(options);
+
+	println!("Rendering on {}.", window.device.adapter_info().name);
+
+	let commands = r#"
+--- Controls ---
+Use Z/Q/S/D to move around.
+Press E or R to rewind one turn, F to redo it.
+Press V to choose which branch of the history F redoes.
+Press B to bookmark the current position, J to jump between bookmarks.
+Press T to reset the current level. It can be rewound like a move.
+Press H for a hint, G to watch the rest of the solution of the level.
+Press space bar to recenter the view. Hold to make the cam follow the player.
+Press ESC to quit.
+"#;
+	print!("{}", commands);
+	play_in(&mut window, game, message, autosave);
+}
+
+/// Play in the window until it's closed. If ESC does not close the window,
+/// it ends the game instead.
+fn play_in(
+	window: &mut PistonWindow,
+	game: &mut game::Game,
+	message: Option<String>,
+	mut autosave: Option<Autosave>,
+) {
+	let mut must_redraw = true;
+	let mut cam_follows = false;
+	// The tile to move to, if the player asked for a hint.
+	let mut hint_target = None::<(usize, usize)>;
+	// The moves left to play when showing a solution, and the time since the last one.
+	let mut solution = VecDeque::<game::Direction>::new();
+	let mut solution_timer = 0.0;
+	// Why the current level cannot be completed anymore, if it can't.
+	let mut deadlocks = Vec::<Deadlock>::new();
+	// What the player is told, over the map.
+	let mut messages = view::Messages::default();
+	if let Some(message) = message {
+		messages.push(message);
+	}
+	let window_size = window.size();
+	let mut viewport = view::Viewport::new(
+		game,
+		(window_size.height as usize, window_size.width as usize),
+	);
+	handle_events(game, window, &mut messages);
+
+	// The font is built into piston_window, hence into the binary.
+	let mut glyphs = window
+		.load_builtin_font(BuiltInFont::HackRegular, wgpu_graphics::TextureSettings::new())
+		.expect("The built-in font is valid.");
+
+	while let Some(event) = window.next() {
+		// The surface is a fresh texture at every frame, so a frame is drawn
+		// only when something changed, the last one staying on screen else.
+		if must_redraw && event.render_args().is_some() {
+			must_redraw = false;
+			window.draw_2d(&event, |context, graphics, _device| {
+				view::draw_all(viewport, game, context, graphics);
+				if let Some(target) = hint_target {
+					view::draw_hint(viewport, game, target, context, graphics);
+				}
+				view::draw_deadlocks(viewport, &deadlocks, context, graphics);
+				view::draw_hud(game, &messages, &mut glyphs, context, graphics);
+			});
+		}
+
+		if let Some(args) = event.update_args() {
+			// The messages fade out, and the frame without them is drawn last.
+			must_redraw |= !messages.is_empty();
+			messages.update(args.dt);
+			if let Some(text) = autosave_message(&mut autosave, game) {
+				messages.push(text);
+			}
+		}
+
+		if let Some(args) = event.update_args()
+			&& !solution.is_empty()
+		{
+			solution_timer += args.dt;
+			if solution_timer >= SOLUTION_STEP {
+				solution_timer = 0.0;
+				let dir = solution.pop_front().unwrap();
+				game.process_player_input(dir);
+				update_deadlocks(game, &mut deadlocks, &mut messages);
+				let teleported = handle_events(game, window, &mut messages);
+				hint_target = solution
+					.front()
+					.and_then(|&next| game.try_step(game.player.0, game.player.1, next));
+				if cam_follows || teleported {
+					viewport.center_around_player(game);
+				}
+				must_redraw = true;
+			}
+		}
+
+		if let Some(args) = event.resize_args() {
+			viewport.resize(args);
+			viewport.center_around_player(game);
+			must_redraw = true;
+		}
+
+		if let Some(Button::Keyboard(key)) = event.press_args() {
+			if key == Key::Escape && !window.get_exit_on_esc() {
+				return;
+			}
+			// Any key stops the solution being shown and clears the hint.
+			solution.clear();
+			hint_target = None;
+			must_redraw = true;
+
+			let has_moved = match key {
+				Key::Z | Key::Up => game.process_player_input(game::Direction::Up),
+				Key::Q | Key::Left => game.process_player_input(game::Direction::Left),
+				Key::S | Key::Down => game.process_player_input(game::Direction::Down),
+				Key::D | Key::Right => game.process_player_input(game::Direction::Right),
+
+				Key::E | Key::R => game.rewind(),
+				Key::F => game.redo(),
+				Key::V => {
+					next_branch(game, &mut messages);
+					false
+				}
+				Key::B => {
+					// The first free name, some may have been deleted.
+					let name = (1..)
+						.map(|n| format!("Bookmark {n}"))
+						.find(|name| game.history_tree().bookmark(name).is_none())
+						.expect("There are fewer bookmarks than numbers.");
+					game.set_bookmark(&name);
+					messages.push(format!("{name} set."));
+					false
+				}
+				Key::J => jump_to_next_bookmark(game, &mut messages),
+
+				Key::T => game.reset_current_level(),
+
+				Key::H | Key::G => {
+					let hint = game.hint(&HINT_LIMITS);
+					messages.push(hint_message(&hint));
+					if let Hint::Move {
+						target,
+						solution: ref moves,
+						..
+					} = hint
+					{
+						hint_target = Some(target);
+						if key == Key::G {
+							solution = moves.chars().filter_map(|c| c.try_into().ok()).collect();
+							solution_timer = 0.0;
+						}
+					}
+					false
+				}
+
+				_ => false,
+			};
+			if has_moved {
+				update_deadlocks(game, &mut deadlocks, &mut messages);
+			}
+			let teleported = handle_events(game, window, &mut messages);
+			if (has_moved && cam_follows) || teleported || key == Key::Space {
+				viewport.center_around_player(game);
+				if key == Key::Space {
+					cam_follows = true;
+				}
+			}
+		}
+
+		if let Some(Button::Keyboard(Key::Space)) = event.release_args() {
+			cam_follows = false;
+		}
+	}
+}
+
+/// Play a history back in a piston window until the viewer closes it.
+pub fn replay(mut replay: Replay, options: &cli::Options) {
+	let mut window = open_window(options);
+	window.set_title(format!("{TITLE} Replay"));
+
+	let commands = r#"
+--- Replay controls ---
+Press space bar to play or pause.
+Use D or Right to step forward, Q or Left to step back.
+Use Z or Up to play faster, S or Down to play slower.
+Type a move number then Enter to go there. Home and End go to the start and the end.
+Press ESC to quit.
+"#;
+	print!("{}", commands);
+
+	let mut must_redraw = true;
+	let mut playing = true;
+	let mut speed = REPLAY_DEFAULT_SPEED;
+	// The time since the last action was played.
+	let mut timer = 0.0;
+	// The move number being typed, to seek to.
+	let mut seek_to = String::new();
+	let mut messages = view::Messages::default();
+	if replay.is_empty() {
+		messages.push("There is nothing to replay.");
+	}
+	let window_size = window.size();
+	let mut viewport = view::Viewport::new(
+		replay.game(),
+		(window_size.height as usize, window_size.width as usize),
+	);
+	let mut glyphs = window
+		.load_builtin_font(BuiltInFont::HackRegular, wgpu_graphics::TextureSettings::new())
+		.expect("The built-in font is valid.");
+
+	while let Some(event) = window.next() {
+		if must_redraw && event.render_args().is_some() {
+			must_redraw = false;
+			window.draw_2d(&event, |context, graphics, _device| {
+				view::draw_all(viewport, replay.game(), context, graphics);
+				view::draw_hud(replay.game(), &messages, &mut glyphs, context, graphics);
+				let state = if playing { "playing" } else { "paused" };
+				let mut status = vec![
+					format!("Replay: move {}/{}", replay.position(), replay.len()),
+					format!("{state}, {} moves per second", REPLAY_SPEEDS[speed]),
+				];
+				if !seek_to.is_empty() {
+					status.push(format!("Go to move {seek_to}"));
+				}
+				view::draw_replay_status(&status, &mut glyphs, context, graphics);
+			});
+		}
+
+		let position = replay.position();
+		if let Some(args) = event.update_args() {
+			must_redraw |= !messages.is_empty();
+			messages.update(args.dt);
+			if playing {
+				timer += args.dt;
+				while timer >= 1.0 / REPLAY_SPEEDS[speed] as f64 {
+					timer -= 1.0 / REPLAY_SPEEDS[speed] as f64;
+					if !replay.step_forward() {
+						playing = false;
+						timer = 0.0;
+						must_redraw = true;
+					}
+				}
+			}
+		}
+
+		if let Some(Button::Keyboard(key)) = event.press_args() {
+			must_redraw = true;
+			if let Some(digit) = u32::from(key)
+				.checked_sub(u32::from(Key::D0))
+				.filter(|&digit| digit < 10)
+			{
+				seek_to.push(char::from_digit(digit, 10).unwrap());
+			}
+			match key {
+				Key::Space => {
+					// Playing from the end starts over.
+					if !playing && replay.is_at_end() {
+						replay.seek(0);
+					}
+					playing = !playing;
+					timer = 0.0;
+				}
+				Key::D | Key::Right => {
+					playing = false;
+					replay.step_forward();
+				}
+				Key::Q | Key::Left => {
+					playing = false;
+					replay.step_back();
+				}
+				Key::Z | Key::Up => speed = (speed + 1).min(REPLAY_SPEEDS.len() - 1),
+				Key::S | Key::Down => speed = speed.saturating_sub(1),
+				Key::Home => replay.seek(0),
+				Key::End => replay.seek(replay.len()),
+				Key::Backspace => _ = seek_to.pop(),
+				Key::Return => {
+					if let Ok(target) = seek_to.parse::<usize>() {
+						replay.seek(target);
+					}
+					seek_to.clear();
+				}
+				_ => {}
+			}
+		}
+
+		if let Some(args) = event.resize_args() {
+			viewport.resize(args);
+			viewport.center_around_player(replay.game());
+			must_redraw = true;
+		}
+		if replay.position() != position {
+			viewport.center_around_player(replay.game());
+			must_redraw = true;
+		}
+	}
+}
+
+fn open_window(options: &cli::Options) -> PistonWindow {
+	let (width, height) = options.window_size;
+	let mut window: PistonWindow = WindowSettings::new(TITLE, [width, height])
+		.exit_on_esc(true)
+		.automatic_close(true)
+		.fullscreen(options.fullscreen)
+		.build()
+		.unwrap();
+
+	window.set_max_fps(options.fps);
+	window.set_ups(100); // 0 disable update-events but also bumps the CPU consumption to 100%.
+	window.set_lazy(false); // if true, the application consumes 100% of my CPU. Very intuitive.
+	return window;
+}
+
+/// Look for deadlocks in the current level and warn about the new ones.
+fn update_deadlocks(
+	game: &game::Game,
+	deadlocks: &mut Vec<Deadlock>,
+	messages: &mut view::Messages,
+) {
+	let found = game.current_level_deadlocks();
+	for deadlock in found.iter().filter(|d| !deadlocks.contains(d)) {
+		messages.push(format!("This level cannot be completed anymore, {deadlock}."));
+	}
+	*deadlocks = found;
+}
+
+/// Tell the player what happened in the game, and show the overall completion in the title.
+/// Return whether the player went through a portal, to follow them there.
+fn handle_events(
+	game: &mut game::Game,
+	window: &mut PistonWindow,
+	messages: &mut view::Messages,
+) -> bool {
+	let mut teleported = false;
+	for event in game.take_events() {
+		teleported |= matches!(event, game::GameEvent::Teleported { .. });
+		if let Some(text) = event_message(game, &event) {
+			messages.push(text);
+		}
+	}
+	let levels = game.levels();
+	window.set_title(format!(
+		"{TITLE} {}/{} snowmen, {}/{} levels",
+		game.snowmen_count(),
+		levels.required_snowmen(),
+		game.progress().completed_count(),
+		levels.len()
+	));
+	return teleported;
+}
+
+/// Make the next branch of the history the one to redo, and list them all.
+fn next_branch(game: &mut game::Game, messages: &mut view::Messages) {
+	let tree = game.history_tree();
+	let branches = tree.children(tree.current());
+	if branches.len() < 2 {
+		messages.push("There is no other branch here.");
+		return;
+	}
+	// The branch to redo is always first, so taking the last one cycles through them.
+	game.select_branch(*branches.last().unwrap());
+	let tree = game.history_tree();
+	for (i, &branch) in tree.children(tree.current()).iter().enumerate() {
+		let marker = if i == 0 { '>' } else { ' ' };
+		let line = tree.redo_line(branch);
+		messages.push(format!(
+			"{marker} {}{line} ({} actions)",
+			tree.action(branch).unwrap(),
+			line.len() + 1
+		));
+	}
+}
+
+/// Jump to the bookmark after the last one the game was at, in name order.
+fn jump_to_next_bookmark(game: &mut game::Game, messages: &mut view::Messages) -> bool {
+	let tree = game.history_tree();
+	let bookmarks: Vec<(String, _)> = tree
+		.bookmarks()
+		.map(|(name, node)| (name.to_owned(), node))
+		.collect();
+	if bookmarks.is_empty() {
+		messages.push("No bookmark yet.");
+		return false;
+	}
+	let current = tree.current();
+	let next = match bookmarks.iter().position(|&(_, node)| node == current) {
+		Some(i) => &bookmarks[(i + 1) % bookmarks.len()],
+		None => &bookmarks[0],
+	};
+	messages.push(format!("Jump to {}.", next.0));
+	return game.jump_to(next.1);
+}
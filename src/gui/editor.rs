@@ -0,0 +1,240 @@
+//!
+//! Edit a map in a piston window, drawn by `view`, and try it in the same
+//! window.
+//!
+
+use std::path::Path;
+
+use piston_window::*;
+
+use snowcrab::editor::{Brush, Editor};
+use snowcrab::{game, save};
+
+use crate::{cli, view};
+
+/// The size of a new map, in rows and columns.
+pub const NEW_MAP_SIZE: (usize, usize) = (16, 24);
+/// The brush selected at first, dirt.
+const DEFAULT_BRUSH: usize = 1;
+
+/// Edit the map in a piston window until the player closes it. The map is
+/// written to `path` with Ctrl+S only, the changes left are discarded.
+pub fn edit(mut editor: Editor, path: &Path, options: &cli::Options) {
+	let mut window = super::open_window(options);
+	// ESC ends the test games, the editor is left with ESC too but by hand.
+	window.set_exit_on_esc(false);
+	let title = format!("{} Editor: {}", super::TITLE, path.display());
+	window.set_title(title.clone());
+
+	let commands = r#"
+--- Editor controls ---
+Paint with the left mouse button, erase with the right one.
+Press 1 to 9 and 0 to choose a brush, Tab to go through them all.
+Press Ctrl+Z to undo, Ctrl+Y to redo.
+Use the arrow keys to scroll, Ctrl and the arrow keys to add or remove rows and columns.
+Press Enter to try the map, ESC to come back.
+Press Ctrl+S to write the map.
+Press ESC to quit, twice if the changes are not written, to discard them:
+the window does not close before.
+"#;
+	print!("{}", commands);
+
+	let mut brush = DEFAULT_BRUSH;
+	// The brush of the mouse button held, if any.
+	let mut painting = None::<Brush>;
+	// The tile under the mouse, if any.
+	let mut cursor = None::<(usize, usize)>;
+	let mut ctrl = false;
+	// The map as it was last read or written, and whether it was edited since.
+	let mut written = editor.to_map_string();
+	let mut modified = false;
+	let mut messages = view::Messages::default();
+	// Whether the player wanted to quit with changes not written, the next
+	// ESC or close discards them.
+	let mut quitting = false;
+	// The edited map is drawn as a fresh game on it.
+	let mut preview = editor.to_game();
+	let window_size = window.size();
+	let mut viewport = view::Viewport::new(
+		&preview,
+		(window_size.height as usize, window_size.width as usize),
+	);
+	let mut glyphs = window
+		.load_builtin_font(BuiltInFont::HackRegular, wgpu_graphics::TextureSettings::new())
+		.expect("The built-in font is valid.");
+
+	let mut must_redraw = true;
+	while let Some(event) = window.next() {
+		if must_redraw && event.render_args().is_some() {
+			must_redraw = false;
+			window.draw_2d(&event, |context, graphics, _device| {
+				view::draw_all(viewport, &preview, context, graphics);
+				if let Some(tile) = cursor {
+					view::draw_cursor(viewport, tile, context, graphics);
+				}
+				let mut status = vec![
+					format!("Brush: {}", Brush::ALL[brush].name()),
+					format!(
+						"Map: {} rows, {} columns{}",
+						editor.size_x(),
+						editor.size_y(),
+						if modified { ", modified" } else { "" }
+					),
+				];
+				if let Some((x, y)) = cursor {
+					status.push(format!("Tile: row {}, column {}", x + 1, y + 1));
+				}
+				view::draw_editor_hud(&status, &messages, &mut glyphs, context, graphics);
+			});
+		}
+
+		if let Some(args) = event.update_args() {
+			must_redraw |= !messages.is_empty();
+			messages.update(args.dt);
+		}
+
+		if let Some(args) = event.resize_args() {
+			viewport.resize(args);
+		}
+		// The mouse, the keys and the window itself may all change the frame.
+		must_redraw |= matches!(event, Event::Input(..));
+
+		let mut edited = false;
+		let mut closing = event.close_args().is_some();
+		if let Some(position) = event.mouse_cursor_args() {
+			cursor = tile_at(viewport, &editor, position);
+			if let (Some(brush), Some(tile)) = (painting, cursor) {
+				edited = editor.paint(tile, brush);
+			}
+		}
+
+		if let Some(Button::Mouse(button)) = event.press_args()
+			&& let Some(tile) = cursor
+		{
+			let pressed = match button {
+				MouseButton::Left => Some(Brush::ALL[brush]),
+				MouseButton::Right => Some(Brush::Tile(game::Tile::Empty)),
+				_ => None,
+			};
+			if let Some(pressed) = pressed {
+				painting = Some(pressed);
+				if !editor.allows(tile, pressed) {
+					messages.push(refusal(pressed));
+				}
+				edited = editor.paint(tile, pressed);
+			}
+		}
+		if let Some(Button::Mouse(_)) = event.release_args() {
+			painting = None;
+			editor.end_stroke();
+		}
+
+		if let Some(Button::Keyboard(key)) = event.release_args()
+			&& matches!(key, Key::LCtrl | Key::RCtrl)
+		{
+			ctrl = false;
+		}
+		if let Some(Button::Keyboard(key)) = event.press_args() {
+			let confirming = std::mem::take(&mut quitting);
+			if let Some(digit) = u32::from(key)
+				.checked_sub(u32::from(Key::D0))
+				.filter(|&digit| digit < 10)
+			{
+				// The keys are in the order of the keyboard, 0 after 9.
+				brush = (digit as usize + 9) % 10;
+			}
+			let (size_x, size_y) = (editor.size_x(), editor.size_y());
+			match key {
+				Key::LCtrl | Key::RCtrl => ctrl = true,
+				Key::Tab => brush = (brush + 1) % Brush::ALL.len(),
+				Key::Z if ctrl => edited = editor.undo() || nothing_to(&mut messages, "undo"),
+				Key::Y if ctrl => edited = editor.redo() || nothing_to(&mut messages, "redo"),
+				Key::S if ctrl => {
+					messages.push(match write(&editor, path) {
+						Ok(()) => {
+							written = editor.to_map_string();
+							modified = false;
+							format!("The map was written to {}.", path.display())
+						}
+						Err(e) => format!("Cannot write the map {}: {e}.", path.display()),
+					});
+				}
+				Key::Up if ctrl => edited = size_x > 1 && editor.resize(size_x - 1, size_y),
+				Key::Down if ctrl => edited = editor.resize(size_x + 1, size_y),
+				Key::Left if ctrl => edited = size_y > 1 && editor.resize(size_x, size_y - 1),
+				Key::Right if ctrl => edited = editor.resize(size_x, size_y + 1),
+				Key::Up => viewport.base_x = viewport.base_x.saturating_sub(1),
+				Key::Down => viewport.base_x = (viewport.base_x + 1).min(size_x.saturating_sub(1)),
+				Key::Left => viewport.base_y = viewport.base_y.saturating_sub(1),
+				Key::Right => viewport.base_y = (viewport.base_y + 1).min(size_y.saturating_sub(1)),
+				Key::Return => {
+					editor.end_stroke();
+					painting = None;
+					let mut game = editor.to_game();
+					let message = "Trying the map. Press ESC to come back to the editor.".to_owned();
+					super::play_in(&mut window, &mut game, Some(message), None);
+					window.set_title(title.clone());
+					// The keys released during the game were not seen.
+					ctrl = false;
+					// The window closed during the game closes the editor, once asked.
+					closing = window.should_close();
+				}
+				Key::Escape if confirming || !modified => break,
+				Key::Escape => {
+					quitting = true;
+					messages.push("The changes are not written: press Ctrl+S to write them, or ESC again to discard them.");
+				}
+				_ => {}
+			}
+		}
+
+		if closing {
+			if std::mem::take(&mut quitting) || !modified {
+				break;
+			}
+			window.set_should_close(false);
+			quitting = true;
+			messages.push("The changes are not written: press Ctrl+S to write them, or close the window again to discard them.");
+		}
+
+		if edited {
+			preview = editor.to_game();
+			modified = editor.to_map_string() != written;
+		}
+		// The window closes by itself only when no change would be lost,
+		// otherwise a close event asks first, as ESC does.
+		window.set_automatic_close(!modified);
+	}
+
+	if modified {
+		println!("The changes to the map {} were discarded.", path.display());
+	}
+}
+
+/// The tile of the map under a position in the window, if any.
+fn tile_at(vp: view::Viewport, editor: &Editor, position: [f64; 2]) -> Option<(usize, usize)> {
+	let x = vp.base_x + (position[1] / view::TILE_SIDE) as usize;
+	let y = vp.base_y + (position[0] / view::TILE_SIDE) as usize;
+	return (x < editor.size_x() && y < editor.size_y()).then_some((x, y));
+}
+
+/// Why the brush cannot be used on a tile.
+fn refusal(brush: Brush) -> &'static str {
+	match brush {
+		Brush::SnowBall(_) => "Snowballs go on dirt or snow, and not on the player's start.",
+		Brush::Portal => "There are nine pairs of portals at most, and not on the player's start.",
+		Brush::Rule(_) | Brush::NoRules => "The rules are those of a level, paint them on one.",
+		_ => "The player's start is an empty tile, move it elsewhere first.",
+	}
+}
+
+/// Tell the player there is nothing to undo or redo.
+fn nothing_to(messages: &mut view::Messages, action: &str) -> bool {
+	messages.push(format!("Nothing to {action}."));
+	return false;
+}
+
+/// Write the map in place: it's the player's own file, which gets no backups.
+fn write(editor: &Editor, path: &Path) -> std::io::Result<()> {
+	save::write_without_backups(path, &editor.to_map_string())
+}
@@ -0,0 +1,18 @@
+//! The rules engine of « A good snowcrab is hard to build ».
+//!
+//! It knows nothing about windows or graphics, so that it can be driven
+//! by any frontend, a test harness or a solver.
+
+// Explicit `return`s are the style of this code base.
+#![allow(clippy::needless_return)]
+
+pub mod editor;
+pub mod game;
+pub mod lint;
+pub mod replay;
+pub mod save;
+
+pub use game::{deadlock, level, tree};
+#[cfg(feature = "solver")]
+pub use game::{hint, solver};
+pub use game::{Direction, Game, SnowBall, Tile};
@@ -0,0 +1,286 @@
+//!
+//! Look for the mistakes of a map in the `map.txt` format, which the game
+//! would load anyway or refuse without telling where.
+//!
+
+use std::collections::VecDeque;
+#[cfg(feature = "solver")]
+use std::time::Instant;
+
+use crate::game::portal::Portals;
+use crate::game::rule;
+use crate::game::{self, Game, MapError};
+#[cfg(feature = "solver")]
+use crate::game::solver::{Limits, SolveError};
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum Severity {
+	/// Something which could not be checked.
+	Note,
+	/// Something the game copes with, likely a mistake though.
+	Warning,
+	/// Something which makes the map wrong or unplayable.
+	Error,
+}
+
+impl std::fmt::Display for Severity {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Severity::Note => write!(f, "note"),
+			Severity::Warning => write!(f, "warning"),
+			Severity::Error => write!(f, "error"),
+		}
+	}
+}
+
+/// A problem at a line and column of the map, both starting at 1.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Diagnostic {
+	pub line: usize,
+	pub column: usize,
+	pub severity: Severity,
+	pub message: String,
+}
+
+impl Diagnostic {
+	fn new<S: Into<String>>((x, y): (usize, usize), severity: Severity, message: S) -> Diagnostic {
+		Diagnostic {
+			line: x + 1,
+			column: y + 1,
+			severity,
+			message: message.into(),
+		}
+	}
+}
+
+impl std::fmt::Display for Diagnostic {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(f, "{}:{}: {}: {}", self.line, self.column, self.severity, self.message)
+	}
+}
+
+/// The problems of the map, in the order of their position:
+/// - rows of different lengths, and the characters without a meaning,
+/// - a missing or duplicate player's start,
+/// - portals without exactly one other end,
+/// - levels without snowballs, or whose balls cannot all make snowmen,
+/// - levels the player cannot walk to from the start,
+/// - rule lines which cannot be read, or which are not about a level.
+pub fn lint(map: &str) -> Vec<Diagnostic> {
+	let mut diagnostics = Vec::new();
+	let (rows, rule_lines) = rule::split_map(map);
+	let rows: Vec<Vec<char>> = rows.iter().map(|row| row.chars().collect()).collect();
+	let width = rows.iter().map(Vec::len).max().unwrap_or(0);
+	if width == 0 {
+		diagnostics.push(Diagnostic::new((0, 0), Severity::Error, MapError::Empty.to_string()));
+		return diagnostics;
+	}
+
+	let mut player = None;
+	for (x, row) in rows.iter().enumerate() {
+		if row.len() < width {
+			let message = format!("the row is {} characters long instead of {width}, the end is empty", row.len());
+			diagnostics.push(Diagnostic::new((x, row.len()), Severity::Warning, message));
+		}
+		for (y, &c) in row.iter().enumerate() {
+			if !game::is_map_char(c) {
+				let message = format!("unknown character {c:?}, read as an empty tile");
+				diagnostics.push(Diagnostic::new((x, y), Severity::Error, message));
+			}
+			if c == 'P' {
+				match player {
+					None => player = Some((x, y)),
+					Some((px, py)) => {
+						let message = format!("another player's start, the first one is at {}:{}", px + 1, py + 1);
+						diagnostics.push(Diagnostic::new((x, y), Severity::Error, message));
+					}
+				}
+			}
+		}
+	}
+	let Some(player) = player else {
+		diagnostics.push(Diagnostic::new((0, 0), Severity::Error, MapError::MissingPlayer.to_string()));
+		return diagnostics;
+	};
+
+	let game = load_first_player(map, player, false).expect("The map has one player's start.");
+	for (number, ends) in (1..).zip(Portals::ends(&game.tiles)) {
+		if ends.len() == 2 {
+			continue;
+		}
+		let message = match ends.len() {
+			1 => format!("portal {number} has no other end, it leads nowhere"),
+			n => format!("portal {number} has {n} ends instead of 2, they lead nowhere"),
+		};
+		for &end in &ends {
+			diagnostics.push(Diagnostic::new(end, Severity::Error, message.clone()));
+		}
+	}
+	let reachable = walkable_from(&game, player);
+	for level in game.levels().iter() {
+		let corner = level.cells[0];
+		let balls: usize = level.snowballs.iter().map(|&(_, snowball)| snowball.ball_count()).sum();
+		if balls == 0 {
+			let message = format!("{} has no snowball", level.name);
+			diagnostics.push(Diagnostic::new(corner, Severity::Error, message));
+		} else if !balls.is_multiple_of(3) {
+			let message = format!(
+				"{} has {balls} ball{}, which do not make snowmen of 3 balls",
+				level.name,
+				if balls == 1 { "" } else { "s" }
+			);
+			diagnostics.push(Diagnostic::new(corner, Severity::Error, message));
+		}
+		if !level.cells.iter().any(|&(x, y)| reachable[x][y]) {
+			let message = format!("{} cannot be reached from the player's start", level.name);
+			diagnostics.push(Diagnostic::new(corner, Severity::Error, message));
+		}
+	}
+	for (index, line) in rule_lines {
+		let message = match rule::parse_line(line) {
+			Err(message) => message,
+			Ok((cell, _)) if game.levels().at(cell).is_none() => {
+				format!("no level at {}:{}, the rules are about the level of that tile", cell.0 + 1, cell.1 + 1)
+			}
+			Ok(_) => continue,
+		};
+		diagnostics.push(Diagnostic::new((index, 0), Severity::Error, message));
+	}
+
+	diagnostics.sort_by_key(|d| (d.line, d.column));
+	return diagnostics;
+}
+
+/// The levels the solver proves unsolvable by their rules, from any of their
+/// entrances, and those it cannot tell within the limits, which are those of
+/// each level. Nothing if the map cannot be loaded.
+#[cfg(feature = "solver")]
+pub fn lint_solutions(map: &str, limits: &Limits) -> Vec<Diagnostic> {
+	let mut diagnostics = Vec::new();
+	let Some(player) = map.lines().enumerate().find_map(|(x, row)| row.chars().position(|c| c == 'P').map(|y| (x, y)))
+	else {
+		return diagnostics;
+	};
+	let Ok(mut game) = load_first_player(map, player, true) else {
+		return diagnostics;
+	};
+	let levels = game.levels().clone();
+	for level in levels.iter() {
+		let corner = level.cells[0];
+		let free = |&&(x, y): &&(usize, usize)| game.snowballs[x][y].is_none();
+		let entrances: Vec<(usize, usize)> = match level.doors.iter().filter(free).count() {
+			0 => level.cells.iter().filter(free).copied().collect(),
+			_ => level.doors.iter().filter(free).copied().collect(),
+		};
+		// The limits are those of the whole level, whatever its entrances.
+		let deadline = limits.max_duration.map(|duration| Instant::now() + duration);
+		let mut outcome = Err(SolveError::Unsolvable);
+		for &entrance in &entrances {
+			let limits = Limits {
+				max_duration: deadline.map(|deadline| deadline.saturating_duration_since(Instant::now())),
+				..*limits
+			};
+			game.player = entrance;
+			match game.solve_current_level(&limits) {
+				Ok(solution) => {
+					outcome = Ok(solution);
+					break;
+				}
+				Err(SolveError::Unsolvable) => {}
+				Err(e) => outcome = Err(e),
+			}
+		}
+		match outcome {
+			Ok(_) => {}
+			Err(SolveError::Unsolvable) => {
+				let message = format!("{} cannot be solved", level.name);
+				diagnostics.push(Diagnostic::new(corner, Severity::Error, message));
+			}
+			Err(e) => {
+				let message = format!("{} was not proven solvable: {e}", level.name);
+				diagnostics.push(Diagnostic::new(corner, Severity::Note, message));
+			}
+		}
+	}
+	return diagnostics;
+}
+
+/// The game on the map where only the first player's start is kept, with
+/// the rules of the map or without them.
+fn load_first_player(map: &str, (px, py): (usize, usize), with_rules: bool) -> Result<Game, MapError> {
+	let (rows, rule_lines) = rule::split_map(map);
+	let rule_lines = rule_lines.into_iter().filter(|_| with_rules).map(|(_, line)| line);
+	let map: String = rows
+		.into_iter()
+		.enumerate()
+		.flat_map(|(x, row)| {
+			let row = row.chars().enumerate().map(move |(y, c)| match c {
+				'P' if (x, y) != (px, py) => ' ',
+				_ => c,
+			});
+			row.chain(std::iter::once('\n'))
+		})
+		.chain(rule_lines.flat_map(|line| line.chars().chain(std::iter::once('\n'))))
+		.collect();
+	return Game::from_map_str(&map);
+}
+
+/// The tiles the player can walk to from `start`, whatever the snowballs in
+/// the way since they can be pushed, and going through the portals.
+fn walkable_from(game: &Game, start: (usize, usize)) -> Vec<Vec<bool>> {
+	let mut seen = vec![vec![false; game.size_y()]; game.size_x()];
+	seen[start.0][start.1] = true;
+	let mut queue = VecDeque::from([start]);
+	while let Some((x, y)) = queue.pop_front() {
+		for dir in [
+			game::Direction::Up,
+			game::Direction::Left,
+			game::Direction::Down,
+			game::Direction::Right,
+		] {
+			if let Some(next) = game.try_step(x, y, dir)
+				&& !game.tiles[next.0][next.1].blocks()
+			{
+				let (nx, ny) = game.portals().exit(next).unwrap_or(next);
+				if !seen[nx][ny] {
+					seen[nx][ny] = true;
+					queue.push_back((nx, ny));
+				}
+			}
+		}
+	}
+	return seen;
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn reports_the_rows_and_the_characters() {
+		let diagnostics = lint("P  ?\n.s,z\n.\n");
+		let found: Vec<(usize, usize, Severity)> = diagnostics.iter().map(|d| (d.line, d.column, d.severity)).collect();
+		assert_eq!(found, [(1, 4, Severity::Error), (3, 2, Severity::Warning)]);
+	}
+
+	#[test]
+	fn reports_the_rule_lines() {
+		let diagnostics = lint("P   \n.s,z\n.\n!level 1:1 no-reset\n");
+		let found: Vec<(usize, usize, Severity)> = diagnostics.iter().map(|d| (d.line, d.column, d.severity)).collect();
+		assert_eq!(found, [(3, 2, Severity::Warning), (4, 1, Severity::Error)]);
+	}
+
+	#[cfg(feature = "solver")]
+	#[test]
+	fn unsolvable_levels_are_errors() {
+		assert_eq!(lint_solutions("P\n.sz\n", &Limits::default()), []);
+		let diagnostics = lint_solutions("P\n.s,z\n", &Limits::default());
+		assert_eq!(diagnostics, [Diagnostic::new((1, 0), Severity::Error, "Level 1 cannot be solved")]);
+		let limits = Limits {
+			max_nodes: 0,
+			..Limits::default()
+		};
+		let diagnostics = lint_solutions("P\n.s,z\n", &limits);
+		assert_eq!(diagnostics[0].severity, Severity::Note);
+	}
+}
@@ -1,118 +1,361 @@
 //!
-//!
+//! The game's binary: the command line, the save file and the frontends. The
+//! piston window needs the `gui` feature, the terminal the `tui` feature.
 //!
 
-// http://docs.piston.rs/piston_window/piston_window/
-use piston_window::*;
+#![allow(clippy::needless_return)]
 
-mod game;
-mod save;
-mod view;
+#[cfg(any(feature = "gui", feature = "tui"))]
+use std::io::IsTerminal;
+#[cfg(any(feature = "gui", feature = "tui"))]
+use std::path::PathBuf;
+#[cfg(any(feature = "gui", feature = "tui"))]
+use std::time::Duration;
 
-const TITLE: &str = "A good snowcrab is hard to build.";
-const SAVE_FILE: &str = "save.txt";
+#[cfg(any(feature = "gui", feature = "tui"))]
+use snowcrab::hint::Hint;
+#[cfg(feature = "editor")]
+use snowcrab::editor::Editor;
+#[cfg(any(feature = "gui", all(feature = "tui", feature = "export")))]
+use snowcrab::replay::Replay;
+#[cfg(any(feature = "gui", feature = "tui"))]
+use snowcrab::save::autosave::Autosave;
+#[cfg(any(feature = "gui", feature = "tui"))]
+use snowcrab::save::slots::{self, Slots};
+#[cfg(any(feature = "gui", feature = "tui"))]
+use snowcrab::{game, save};
+
+#[cfg(any(feature = "gui", feature = "tui"))]
+mod cli;
+#[cfg(all(any(feature = "gui", feature = "tui"), feature = "export"))]
+mod export;
+#[cfg(feature = "gui")]
+mod gui;
+#[cfg(any(feature = "gui", feature = "tui"))]
+mod palette;
+#[cfg(feature = "tui")]
+mod tui;
+#[cfg(any(feature = "gui", all(feature = "tui", feature = "export")))]
+mod view;
 
+#[cfg(any(feature = "gui", feature = "tui"))]
 fn main() {
-	let mut window: PistonWindow = WindowSettings::new(TITLE, [1200, 800])
-		.exit_on_esc(true)
-		.automatic_close(true)
-		.build()
-		.unwrap();
-
-	window.set_max_fps(10);
-	window.set_ups(100); // 0 disable update-events but also bumps the CPU consumption to 100%.
-	window.set_lazy(false); // if true, the application consumes 100% of my CPU. Very intuitive.
-
-	println!(
-		"Rendering on {}.",
-		window.device.get_info().platform_name.renderer
-	);
-
-	let commands = r#"
---- Controls ---
-Use Z/Q/S/D to move around.
-Press E or R to rewind one turn.
-Press T to reset the current level. (WARNING: it erases the rewind memory)
-Press space bar to recenter the view. Hold to make the cam follow the player.
-Press ESC to quit.
-"#;
-	print!("{}", commands);
-
-	let mut must_redraw = true;
-	let mut cam_follows = false;
-	let mut game = game::Game::instanciate();
-	let mut viewport = view::Viewport::new(&game, (1200, 800));
+	let args: Vec<String> = std::env::args().skip(1).collect();
+	let mut options = match cli::parse(args.iter().cloned()) {
+		Ok(cli::Command::Play(options)) => options,
+		Ok(cli::Command::Slots(command)) => {
+			if let Err(e) = manage_slots(command) {
+				eprintln!("{e}.");
+				std::process::exit(1);
+			}
+			return;
+		}
+		Ok(cli::Command::Help) => {
+			print!("{}", cli::USAGE);
+			return;
+		}
+		Ok(cli::Command::Version) => {
+			println!("snowcrab {}", env!("CARGO_PKG_VERSION"));
+			return;
+		}
+		Err(e) => {
+			eprintln!("{e}\n\n{}", cli::USAGE);
+			std::process::exit(2);
+		}
+	};
+
+	#[cfg(feature = "editor")]
+	if let Some(ref path) = options.edit {
+		edit_map(path, &options);
+		return;
+	}
+
+	let mut game = match options.map {
+		Some(ref path) => match game::Game::from_map_file(path) {
+			Ok(game) => game,
+			Err(e) => {
+				eprintln!("Cannot load the map {}: {e}.", path.display());
+				std::process::exit(1);
+			}
+		},
+		None => game::Game::instanciate(),
+	};
 
 	// Attempt to load the last game's save.
-	if std::path::Path::new(SAVE_FILE).exists() {
-		if let Err(e) = save::load(&mut game, SAVE_FILE) {
-			println!("Error when loading the save file {SAVE_FILE}: {:?}.", e);
-		} else {
-			println!("Previous save loaded.");
+	let mut save_file = save_file(&options);
+	if options.export_png.is_some() || options.export_gif.is_some() {
+		#[cfg(feature = "export")]
+		export_images(game, save_file, &options);
+		return;
+	}
+	if options.replay {
+		#[cfg(feature = "gui")]
+		watch_replay(game, save_file, &options);
+		return;
+	}
+	let mut message = None;
+	if let Some(path) = save_file.clone()
+		&& !options.new_game
+	{
+		let loaded = match options.verify_save {
+			true => save::load_replaying(&mut game, &path),
+			false => save::load(&mut game, &path),
+		};
+		message = match loaded {
+			// There is no save yet.
+			Err(save::LoadError::Io(e)) if e.kind() == std::io::ErrorKind::NotFound => None,
+			Err(e) => Some({
+				// Writing over it would lose it for good.
+				save_file = None;
+				format!(
+					"Error when loading the save file {}: {e}. It will not be overwritten.",
+					path.display()
+				)
+			}),
+			Ok(info) => {
+				// The command line takes precedence over the saved settings.
+				let saved = cli::Options::with_settings(&info.settings);
+				if let Ok(cli::Command::Play(parsed)) = cli::parse_over(saved, args) {
+					options = parsed;
+				}
+				Some(if let Some(backup) = info.backup {
+					format!("The save file was corrupt, its backup {} was loaded.", backup.display())
+				} else if info.format < save::FORMAT_VERSION {
+					"Previous save loaded, it will be written in the new format.".to_owned()
+				} else {
+					"Previous save loaded.".to_owned()
+				})
+			}
+		};
+	}
+
+	let autosave = save_file.clone().map(|path| {
+		let every_actions = (options.autosave_actions > 0).then_some(options.autosave_actions);
+		let every = (options.autosave_secs > 0).then(|| Duration::from_secs(options.autosave_secs));
+		Autosave::new(path, options.settings(), options.snapshot, every_actions, every)
+	});
+	if options.tui {
+		#[cfg(feature = "tui")]
+		if let Err(e) = tui::play(&mut game, message, autosave) {
+			eprintln!("Error with the terminal: {e}.");
 		}
+	} else {
+		#[cfg(feature = "gui")]
+		gui::play(&mut game, &options, message, autosave);
 	}
 
-	// let texture_context = window.create_texture_context();
-	// let mut glyph =
-	// 	piston_window::Glyphs::from_bytes(FONT_DATA, texture_context, TextureSettings::new())
-	// 		.unwrap();
+	// save the current game
+	if let Some(ref save_file) = save_file {
+		let saved = match options.snapshot {
+			true => save::save_with_snapshot(&game, &options.settings(), save_file),
+			false => save::save(&game, &options.settings(), save_file),
+		};
+		match saved {
+			Err(e) => println!("Couldn't save file {}: {:?}", save_file.display(), e.kind()),
+			Ok(true) => println!("Game was saved to {}.", save_file.display()),
+			Ok(false) => {}
+		}
+	}
 
-	while let Some(event) = window.next() {
-		// It draws only if the event is a render event. The check is automatic.
-		window.draw_2d(&event, |context, graphics, _device| {
-			if must_redraw {
-				must_redraw = false;
-				view::draw_all(viewport, &game, context, graphics);
-			}
-		});
+	let snowmen_count = game.snowmen_count();
+	println!("Number of snowmen: {snowmen_count}. ⛄️");
+}
 
-		if let Some(args) = event.resize_args() {
-			viewport.resize(args);
-			viewport.center_around_player(&game);
-			must_redraw = true;
+/// Edit the map in the file, or a new one if there is no such file.
+#[cfg(feature = "editor")]
+fn edit_map(path: &std::path::Path, options: &cli::Options) {
+	let editor = match std::fs::read_to_string(path) {
+		Ok(map) => Editor::from_map_str(&map).map_err(|e| e.to_string()),
+		Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+			let (size_x, size_y) = gui::editor::NEW_MAP_SIZE;
+			Ok(Editor::new(size_x, size_y))
+		}
+		Err(e) => Err(e.to_string()),
+	};
+	match editor {
+		Ok(editor) => gui::editor::edit(editor, path, options),
+		Err(e) => {
+			eprintln!("Cannot load the map {}: {e}.", path.display());
+			std::process::exit(1);
 		}
+	}
+}
 
-		if let Some(Button::Keyboard(key)) = event.press_args() {
-			let has_moved = match key {
-				Key::Z | Key::Up => game.process_player_input(game::Direction::Up),
-				Key::Q | Key::Left => game.process_player_input(game::Direction::Left),
-				Key::S | Key::Down => game.process_player_input(game::Direction::Down),
-				Key::D | Key::Right => game.process_player_input(game::Direction::Right),
+/// Play the history of the save back in a window. Nothing is saved.
+#[cfg(feature = "gui")]
+fn watch_replay(game: game::Game, save_file: Option<PathBuf>, options: &cli::Options) {
+	let Some(path) = save_file else {
+		eprintln!("There is no save file to replay.");
+		std::process::exit(1);
+	};
+	let mut saved = game.clone();
+	if let Err(e) = save::load(&mut saved, &path) {
+		eprintln!("Error when loading the save file {}: {e}.", path.display());
+		std::process::exit(1);
+	}
+	match Replay::new(game, saved.get_history()) {
+		Ok(replay) => gui::replay(replay, options),
+		Err(c) => {
+			eprintln!("Invalid action in the save file {}: {c:?}.", path.display());
+			std::process::exit(1);
+		}
+	}
+}
 
-				Key::E | Key::R => game.rewind(),
+/// Draw the save in the image files of the options. Nothing is saved.
+#[cfg(all(any(feature = "gui", feature = "tui"), feature = "export"))]
+fn export_images(game: game::Game, save_file: Option<PathBuf>, options: &cli::Options) {
+	let mut saved = game.clone();
+	if let Some(ref path) = save_file
+		&& let Err(e) = save::load(&mut saved, path)
+	{
+		// Without a save, the images are of the start of the game.
+		if !matches!(&e, save::LoadError::Io(e) if e.kind() == std::io::ErrorKind::NotFound) {
+			eprintln!("Error when loading the save file {}: {e}.", path.display());
+			std::process::exit(1);
+		}
+	}
+	if let Some(ref path) = options.export_png {
+		match export::write_png(&saved, options.export_tile, path) {
+			Ok(()) => println!("The board was drawn in {}.", path.display()),
+			Err(e) => {
+				eprintln!("Cannot write the image {}: {e}.", path.display());
+				std::process::exit(1);
+			}
+		}
+	}
+	if let Some(ref path) = options.export_gif {
+		let mut replay = Replay::new(game, saved.get_history()).expect("A loaded history is valid.");
+		match export::write_gif(&mut replay, options.export_tile, path) {
+			Ok(()) => println!("The {} moves were drawn in {}.", replay.len(), path.display()),
+			Err(e) => {
+				eprintln!("Cannot write the animation {}: {e}.", path.display());
+				std::process::exit(1);
+			}
+		}
+	}
+}
 
-				Key::T => game.reset_current_level(),
+/// The file to load and save the game, if any. Without a slot directory,
+/// it's `save.txt` in the working directory, as in older versions.
+#[cfg(any(feature = "gui", feature = "tui"))]
+fn save_file(options: &cli::Options) -> Option<PathBuf> {
+	let name = match options.save {
+		cli::SaveChoice::Nowhere => return None,
+		cli::SaveChoice::File(ref path) => return Some(path.clone()),
+		cli::SaveChoice::Slot(ref name) => Some(name.clone()),
+		cli::SaveChoice::Pick => None,
+	};
+	let slots = match Slots::open_default() {
+		Ok(slots) => slots,
+		Err(e) => {
+			eprintln!("Cannot open the save slots: {e}. Saving to save.txt instead.");
+			return Some(PathBuf::from("save.txt"));
+		}
+	};
+	let names = slots.list().unwrap_or_default();
+	let name = match name {
+		Some(name) => name,
+		None if names.is_empty() => {
+			// The first time, the save of older versions becomes the default slot.
+			let old_save = PathBuf::from("save.txt");
+			let default = slots.path(slots::DEFAULT_SLOT).unwrap();
+			if old_save.exists() && std::fs::copy(&old_save, &default).is_ok() {
+				println!("{} was imported in the slot {}.", old_save.display(), slots::DEFAULT_SLOT);
+			}
+			slots::DEFAULT_SLOT.to_owned()
+		}
+		// There is nothing to choose from, a new slot is made with --slot.
+		None if names.len() == 1 => names[0].clone(),
+		None if !std::io::stdin().is_terminal() => slots::DEFAULT_SLOT.to_owned(),
+		None => match cli::pick_slot(&slots, &names) {
+			Ok(name) => name,
+			Err(e) => {
+				eprintln!("Cannot read the slot to play in: {e}.");
+				std::process::exit(1);
+			}
+		},
+	};
+	match slots.path(&name) {
+		Ok(path) => return Some(path),
+		Err(e) => {
+			eprintln!("{e}.");
+			std::process::exit(2);
+		}
+	}
+}
 
-				_ => false,
-			};
-			if (has_moved && cam_follows) || key == Key::Space {
-				viewport.center_around_player(&game);
-				must_redraw = true;
-				if key == Key::Space {
-					cam_follows = true;
-				}
-			} else if has_moved {
-				must_redraw = true;
+#[cfg(any(feature = "gui", feature = "tui"))]
+fn manage_slots(command: cli::SlotCommand) -> std::io::Result<()> {
+	let slots = Slots::open_default()?;
+	match command {
+		cli::SlotCommand::List => {
+			for name in slots.list()? {
+				println!("{name}");
 			}
 		}
+		cli::SlotCommand::Copy { from, to } => slots.copy(&from, &to)?,
+		cli::SlotCommand::Rename { from, to } => slots.rename(&from, &to)?,
+		cli::SlotCommand::Delete(name) => slots.delete(&name)?,
+	}
+	return Ok(());
+}
 
-		if let Some(Button::Keyboard(Key::Space)) = event.release_args() {
-			cam_follows = false;
+/// Without a frontend, only the library and the other tools are of any use.
+#[cfg(not(any(feature = "gui", feature = "tui")))]
+fn main() {
+	eprintln!("snowcrab was built without a frontend, enable the `gui` or the `tui` feature.");
+	std::process::exit(1);
+}
+
+/// What to tell the player about the event, if anything.
+#[cfg(any(feature = "gui", feature = "tui"))]
+fn event_message(game: &game::Game, event: &game::GameEvent) -> Option<String> {
+	match *event {
+		game::GameEvent::LevelCompleted {
+			level,
+			moves,
+			new_best,
+		} => {
+			let name = &game.levels().get(level).unwrap().name;
+			let best = if new_best { " New record!" } else { "" };
+			return Some(format!("{name} completed in {moves} moves.{best}"));
 		}
+		// Bumping into things, building snowmen and going through portals can be seen on the map.
+		game::GameEvent::MoveBlocked {
+			reason: game::BlockReason::OutsideMap,
+			..
+		} => return Some(event.to_string()),
+		game::GameEvent::MoveBlocked { .. }
+		| game::GameEvent::SnowmanBuilt { .. }
+		| game::GameEvent::Teleported { .. } => return None,
+		_ => return Some(event.to_string()),
 	}
+}
 
-	// save the current game
-	match save::save(&game, SAVE_FILE) {
-		Err(e) => println!("Couldn't save file {SAVE_FILE}: {:?}", e.kind()),
-		Ok(true) => println!("Game was saved to {SAVE_FILE}."),
-		Ok(false) => {}
-	}
-
-	let snowmen_count = game
-		.snowballs
-		.iter()
-		.flatten()
-		.filter(|o| **o == Some(game::SnowBall::Snowman))
-		.count();
-	println!("Number of snowmen: {snowmen_count}. ⛄️");
+/// What to tell the player about an autosave, if anything.
+#[cfg(any(feature = "gui", feature = "tui"))]
+fn autosave_message(autosave: &mut Option<Autosave>, game: &game::Game) -> Option<String> {
+	match autosave.as_mut()?.update(game)? {
+		Err(e) => return Some(format!("Autosave failed: {e}.")),
+		Ok(_) => return None,
+	}
+}
+
+#[cfg(any(feature = "gui", feature = "tui"))]
+fn hint_message(hint: &Hint) -> String {
+	match hint {
+		Hint::Move { dir, solution, .. } => format!(
+			"Hint: move {dir:?}. The level can be completed in {} moves.",
+			solution.len()
+		),
+		Hint::Solved => "This level is already complete.".to_owned(),
+		Hint::Unsolvable { rewinds: Some(n) } => format!(
+			"This level cannot be completed anymore. Rewind {n} time(s) to get back to a solvable state."
+		),
+		Hint::Unsolvable { rewinds: None } => {
+			"This level cannot be completed anymore, even by rewinding. Try to reset it.".to_owned()
+		}
+		Hint::Unknown(e) => format!("No hint: {e}."),
+	}
 }
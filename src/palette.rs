@@ -0,0 +1,44 @@
+//!
+//! The colors of the board, the same in the window, the images and the
+//! terminal.
+//!
+
+use snowcrab::game::Tile;
+
+/// The colors of the portals by number, the same for the two linked ones.
+const PORTAL_COLORS: [[f32; 4]; 9] = [
+	[0.9375, 0.25, 0.25, 1.0],
+	[0.25, 0.5, 0.9375, 1.0],
+	[0.9375, 0.8125, 0.125, 1.0],
+	[0.6875, 0.3125, 0.875, 1.0],
+	[0.25, 0.875, 0.5, 1.0],
+	[0.9375, 0.5, 0.125, 1.0],
+	[0.125, 0.8125, 0.875, 1.0],
+	[0.9375, 0.4375, 0.75, 1.0],
+	[1.0, 1.0, 1.0, 1.0],
+];
+/// The color of a portal without its other end.
+const PORTAL_UNLINKED_COLOR: [f32; 4] = [0.25, 0.25, 0.25, 1.0];
+pub const PLAYER_COLOR: [f32; 4] = [0.75, 0.0625, 0.125, 1.0];
+
+/// The background color of a tile.
+pub fn tile_color(tile: Tile) -> [f32; 4] {
+	match tile {
+		Tile::Empty => [0.375, 0.375, 0.375, 1.0],
+		Tile::Dirt => [0.625, 0.4375, 0.125, 1.0],
+		Tile::Snow => [0.8125, 0.8125, 0.875, 1.0],
+		Tile::Hedge => [0.0625, 0.375, 0.0, 1.0],
+		Tile::Tree => [0.125, 0.75, 0.25, 1.0],
+		Tile::Obstacle => [0.375, 0.25, 0.375, 1.0],
+		Tile::Ice => [0.6875, 0.875, 0.9375, 1.0],
+		Tile::Portal(_) => [0.375, 0.375, 0.375, 1.0],
+	}
+}
+
+/// The color of the portal of that number, grey if it leads nowhere.
+pub fn portal_color(number: u8, linked: bool) -> [f32; 4] {
+	match linked {
+		true => PORTAL_COLORS[(number as usize).saturating_sub(1) % PORTAL_COLORS.len()],
+		false => PORTAL_UNLINKED_COLOR,
+	}
+}
@@ -0,0 +1,159 @@
+//!
+//! Play a history back, one action at a time, in both directions. It's the
+//! game of a fresh map on which the actions are performed or rewound.
+//!
+
+use crate::game::{Direction, Game};
+
+/// How far back seeking is done by rewinding rather than replaying from the start.
+const MAX_REWINDS: usize = 2048;
+
+#[derive(Clone, Debug)]
+pub struct Replay {
+	/// The game before any action.
+	start: Game,
+	game: Game,
+	actions: Vec<char>,
+}
+
+impl Replay {
+	/// A replay of `actions` on `game`, where nothing must have been played yet.
+	/// Fail on the first character which is not an action.
+	pub fn new(game: Game, actions: &str) -> Result<Replay, char> {
+		if let Some(c) = actions.chars().find(|c| !matches!(c, 'U' | 'L' | 'D' | 'R' | 'T')) {
+			return Err(c);
+		}
+		let replay = Replay {
+			start: game.clone(),
+			game,
+			actions: actions.chars().collect(),
+		};
+		return Ok(replay);
+	}
+
+	/// The game after the actions played back so far.
+	pub fn game(&self) -> &Game {
+		&self.game
+	}
+
+	/// The number of actions played back so far.
+	pub fn position(&self) -> usize {
+		self.game.get_history().len()
+	}
+
+	pub fn len(&self) -> usize {
+		self.actions.len()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.actions.is_empty()
+	}
+
+	pub fn is_at_end(&self) -> bool {
+		self.position() == self.len()
+	}
+
+	/// Play the next action. Return `false` at the end, or if the action
+	/// cannot be played: the history was not made on this map, or with
+	/// these rules. The replay ends there.
+	pub fn step_forward(&mut self) -> bool {
+		let Some(&action) = self.actions.get(self.position()) else {
+			return false;
+		};
+		let performed = match Direction::try_from(action) {
+			Ok(dir) => self.game.process_player_input(dir),
+			Err(_) => self.game.reset_current_level(),
+		};
+		if !performed {
+			self.actions.truncate(self.position());
+		}
+		// The frontend shows what happens through the board only.
+		self.game.take_events();
+		return performed;
+	}
+
+	/// Go back one action. Return `false` at the start.
+	pub fn step_back(&mut self) -> bool {
+		let rewound = self.game.rewind();
+		self.game.take_events();
+		return rewound;
+	}
+
+	/// Go to the state after `position` actions, or the last one if there are
+	/// not as many.
+	pub fn seek(&mut self, position: usize) {
+		let position = position.min(self.len());
+		if position + MAX_REWINDS < self.position() {
+			self.game = self.start.clone();
+		}
+		while self.position() > position {
+			self.game.rewind();
+		}
+		while self.position() < position && self.step_forward() {}
+		self.game.take_events();
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/// A level under room to walk around.
+	const MAP: &str = "P   \n    \n.sx \n";
+
+	fn replay(actions: &str) -> Replay {
+		Replay::new(Game::from_map_str(MAP).unwrap(), actions).unwrap()
+	}
+
+	#[test]
+	fn only_actions_are_replayed() {
+		let game = Game::from_map_str(MAP).unwrap();
+		assert_eq!(Replay::new(game, "DDx").unwrap_err(), 'x');
+		assert!(replay("").is_empty());
+	}
+
+	#[test]
+	fn steps_go_both_ways() {
+		let mut replay = replay("RDL");
+		assert!(replay.step_forward());
+		assert!(replay.step_forward());
+		assert_eq!((replay.position(), replay.game().player), (2, (1, 1)));
+		assert!(replay.step_back());
+		assert_eq!((replay.position(), replay.game().player), (1, (0, 1)));
+		assert!(replay.step_forward());
+		assert!(replay.step_forward());
+		assert!(replay.is_at_end());
+		assert!(!replay.step_forward());
+		assert_eq!(replay.game().player, (1, 0));
+	}
+
+	#[test]
+	fn the_replay_ends_at_an_action_which_cannot_be_played() {
+		// The snowman built by the last of these moves, right under the player,
+		// cannot be pushed.
+		let map = "P    \n s.  \n m.  \n l.  \n";
+		let game = Game::from_map_str(map).unwrap();
+		let mut replay = Replay::new(game, "DRDUURDRDLULDDL").unwrap();
+		replay.seek(usize::MAX);
+		assert_eq!(replay.len(), 13);
+		assert!(replay.is_at_end());
+		assert!(replay.game().progress().record(crate::game::level::LevelId(0)).is_some());
+	}
+
+	#[test]
+	fn seeking_goes_to_the_state_after_as_many_actions() {
+		let mut replay = replay("RRDLT");
+		replay.seek(3);
+		assert_eq!(replay.game().player, (1, 2));
+		replay.seek(1);
+		assert_eq!(replay.game().player, (0, 1));
+		replay.seek(5);
+		assert!(replay.is_at_end());
+		let mut fresh = Replay::new(Game::from_map_str(MAP).unwrap(), "RRDLT").unwrap();
+		while fresh.step_forward() {}
+		assert_eq!(replay.game().get_history(), fresh.game().get_history());
+		assert_eq!(replay.game().player, fresh.game().player);
+		replay.seek(0);
+		assert_eq!((replay.position(), replay.game().player), (0, (0, 0)));
+	}
+}
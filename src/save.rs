@@ -1,25 +1,605 @@
 //!
+//! Save and load a game. A save file starts with a header, ended by an empty line:
 //!
+//! ```text
+//! snowcrab save 2
+//! game 0.2.0
+//! map 9c1f5e0b7a3d2c41
+//! setting fps 10
+//! ```
 //!
+//! `map` is the `Game::map_hash` of the map the game was played on: a save
+//! cannot be loaded on another map. The `setting` lines are the frontend's,
+//! the library only keeps them.
+//!
+//! The body is the string of the player's inputs. The other lines of play, if
+//! there are any, follow on a line starting with `%` (see `HistoryTree::serialize`),
+//! and each bookmark on a line `@<path> <name>`. Each completed level has a
+//! line `*<level index> <first moves> <best moves>`. A line starting with `>`
+//! lists actions to redo, as older saves did. The lines starting with `$`, if
+//! any, are a snapshot of the board after the history (see `Snapshot::serialize`),
+//! which is loaded instead of replaying the history.
+//!
+//! The saves of older versions are only the body, without any header. They are
+//! format 1, loaded without checking the map, and written in the current format
+//! from then on.
+//!
+//! A save is written to a temporary file renamed over the previous one, which
+//! is kept as a backup `<file>.1`, the one before as `<file>.2`, and so on.
+//! The previous one is linked as the backup first, so that a crash at any
+//! point leaves a save file.
+//! If the file is missing or corrupt, `load` falls back to the newest backup
+//! it can read.
+//!
+
+pub mod autosave;
+pub mod slots;
+
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use crate::game::level::LevelId;
+use crate::game::progress::LevelRecord;
+use crate::game::snapshot::{Snapshot, SnapshotError};
 
-use std::path::Path;
+/// The format written by `save`.
+pub const FORMAT_VERSION: u32 = 2;
+const MAGIC: &str = "snowcrab save ";
+/// The number of previous saves kept next to the save file.
+pub const BACKUPS: usize = 3;
 
-pub fn load<P: AsRef<Path>>(game: &mut crate::game::Game, from: P) -> std::io::Result<()> {
-	let history = std::fs::read_to_string(from)?;
+/// The frontend's settings, by name. Names cannot contain spaces.
+pub type Settings = BTreeMap<String, String>;
 
-	game.apply_history(&history).map_err(|c| {
-		std::io::Error::new(
-			std::io::ErrorKind::Other,
-			format!("Unknown character: {c:?}."),
-		)
-	})?;
-	Ok(())
+/// What the header of a save file told.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct SaveInfo {
+	/// `1` for the plain saves of older versions.
+	pub format: u32,
+	/// The version of the game which wrote the file, unknown for format 1.
+	pub game_version: Option<String>,
+	pub settings: Settings,
+	/// The backup which was loaded, if the save file itself was missing or corrupt.
+	pub backup: Option<PathBuf>,
+}
+
+/// Why a save file could not be loaded.
+#[derive(Debug)]
+pub enum LoadError {
+	Io(std::io::Error),
+	/// The file was written by a newer version of the game.
+	UnsupportedFormat(u32),
+	/// A line of the header is malformed, or the map's hash is missing.
+	InvalidHeader(String),
+	/// The save was made on another map.
+	MapMismatch { game_version: Option<String> },
+	/// An action of the history is not one of `ULDRT`.
+	UnknownAction(char),
+	InvalidRecord(String),
+	Snapshot(SnapshotError),
+}
+
+impl std::fmt::Display for LoadError {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			LoadError::Io(e) => write!(f, "cannot read the save: {e}"),
+			LoadError::UnsupportedFormat(format) => write!(
+				f,
+				"the save is in format {format}, this version of the game only knows up to {FORMAT_VERSION}"
+			),
+			LoadError::InvalidHeader(line) => write!(f, "invalid header line: {line:?}"),
+			LoadError::MapMismatch { game_version } => {
+				write!(f, "the save was made on another map")?;
+				if let Some(version) = game_version {
+					write!(f, ", by snowcrab {version}")?;
+				}
+				Ok(())
+			}
+			LoadError::UnknownAction(c) => write!(f, "unknown action in the history: {c:?}"),
+			LoadError::InvalidRecord(record) => write!(f, "invalid level record: {record:?}"),
+			LoadError::Snapshot(e) => write!(f, "{e}"),
+		}
+	}
+}
+
+impl std::error::Error for LoadError {
+	fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+		match self {
+			LoadError::Io(e) => Some(e),
+			_ => None,
+		}
+	}
+}
+
+impl From<std::io::Error> for LoadError {
+	fn from(e: std::io::Error) -> Self {
+		LoadError::Io(e)
+	}
+}
+
+/// Load the save file, or its newest valid backup if it's missing or corrupt.
+/// On error, the game is left as it was and the error is the file's own.
+pub fn load<P: AsRef<Path>>(
+	game: &mut crate::game::Game,
+	from: P,
+) -> Result<SaveInfo, LoadError> {
+	return load_with_backups(game, from.as_ref(), false);
+}
+
+/// Like `load`, but replay the history even if there is a snapshot, and
+/// check the snapshot matches the board then.
+pub fn load_replaying<P: AsRef<Path>>(
+	game: &mut crate::game::Game,
+	from: P,
+) -> Result<SaveInfo, LoadError> {
+	return load_with_backups(game, from.as_ref(), true);
+}
+
+fn load_with_backups(
+	game: &mut crate::game::Game,
+	from: &Path,
+	replay: bool,
+) -> Result<SaveInfo, LoadError> {
+	let initial = game.clone();
+	let error = match load_file(game, from, replay) {
+		Ok(info) => return Ok(info),
+		// The backups are from the same game, they would fail the same way.
+		Err(e @ (LoadError::MapMismatch { .. } | LoadError::UnsupportedFormat(_))) => {
+			*game = initial;
+			return Err(e);
+		}
+		Err(e) => e,
+	};
+	for i in 1..=BACKUPS {
+		*game = initial.clone();
+		let backup = backup_path(from, i);
+		if let Ok(mut info) = load_file(game, &backup, replay) {
+			info.backup = Some(backup);
+			return Ok(info);
+		}
+	}
+	*game = initial;
+	return Err(error);
 }
 
-pub fn save<P: AsRef<Path>>(game: &crate::game::Game, to: P) -> std::io::Result<bool> {
-	if !game.get_history().is_empty() {
-		std::fs::write(to, game.get_history()).map(|_| true)
+/// The path of the `n`th most recent backup of a save file, from 1.
+pub fn backup_path<P: AsRef<Path>>(path: P, n: usize) -> PathBuf {
+	let mut path = path.as_ref().as_os_str().to_owned();
+	path.push(format!(".{n}"));
+	return PathBuf::from(path);
+}
+
+fn load_file(
+	game: &mut crate::game::Game,
+	from: &Path,
+	replay: bool,
+) -> Result<SaveInfo, LoadError> {
+	let content = std::fs::read_to_string(from)?;
+	let (info, body) = if content.starts_with(MAGIC) {
+		parse_header(game, &content)?
 	} else {
-		Ok(false)
+		let info = SaveInfo {
+			format: 1,
+			game_version: None,
+			settings: Settings::new(),
+			backup: None,
+		};
+		(info, content.as_str())
+	};
+
+	let mut history = String::new();
+	let mut redo = "";
+	let mut bookmarks = Vec::new();
+	let mut snapshot = Vec::new();
+	for line in body.lines() {
+		if let Some(line) = line.strip_prefix('$') {
+			snapshot.push(line);
+		} else if let Some(tree) = line.strip_prefix('%') {
+			game.insert_history_tree(tree).map_err(LoadError::UnknownAction)?;
+		} else if let Some(bookmark) = line.strip_prefix('@') {
+			let (path, name) = bookmark.split_once(' ').unwrap_or((bookmark, ""));
+			bookmarks.push((path, name));
+		} else if let Some(record) = line.strip_prefix('*') {
+			let (level, record) = parse_record(record, game.levels().len())
+				.ok_or_else(|| LoadError::InvalidRecord(record.to_owned()))?;
+			game.progress_mut().insert(level, record);
+		} else if let Some(line) = line.strip_prefix('>') {
+			redo = line;
+		} else {
+			history.push_str(line);
+		}
+	}
+
+	let snapshot = match snapshot.is_empty() {
+		true => None,
+		false => Some(Snapshot::parse(snapshot).map_err(LoadError::Snapshot)?),
+	};
+	match snapshot {
+		Some(ref snapshot) if !replay => {
+			game.apply_snapshot(&history, snapshot).map_err(LoadError::Snapshot)?;
+		}
+		_ => {
+			game.apply_history(&history).map_err(LoadError::UnknownAction)?;
+			if snapshot.is_some_and(|snapshot| snapshot != game.snapshot()) {
+				return Err(LoadError::Snapshot(SnapshotError::Mismatch));
+			}
+		}
+	}
+	game.set_redo(redo).map_err(LoadError::UnknownAction)?;
+	for (path, name) in bookmarks {
+		game.insert_bookmark(name, path).map_err(LoadError::UnknownAction)?;
+	}
+	Ok(info)
+}
+
+/// Write the game in the current format, unless nothing was played yet.
+pub fn save<P: AsRef<Path>>(
+	game: &crate::game::Game,
+	settings: &Settings,
+	to: P,
+) -> std::io::Result<bool> {
+	return write(game, settings, to.as_ref(), false);
+}
+
+/// Like `save`, with a snapshot of the board so that `load` doesn't replay the history.
+pub fn save_with_snapshot<P: AsRef<Path>>(
+	game: &crate::game::Game,
+	settings: &Settings,
+	to: P,
+) -> std::io::Result<bool> {
+	return write(game, settings, to.as_ref(), true);
+}
+
+fn write(
+	game: &crate::game::Game,
+	settings: &Settings,
+	to: &Path,
+	snapshot: bool,
+) -> std::io::Result<bool> {
+	let tree = game.history_tree();
+	if tree.is_empty() {
+		return Ok(false);
+	}
+	let mut content = format!(
+		"{MAGIC}{FORMAT_VERSION}\ngame {}\nmap {:016x}\n",
+		env!("CARGO_PKG_VERSION"),
+		game.map_hash()
+	);
+	for (name, value) in settings {
+		content.push_str(&format!("setting {name} {value}\n"));
+	}
+	content.push('\n');
+
+	content.push_str(game.get_history());
+	// Only the current line of play is in the history otherwise.
+	if tree.len() > game.get_history().len() + 1 {
+		content.push_str("\n%");
+		content.push_str(&tree.serialize());
+	}
+	for (name, node) in tree.bookmarks() {
+		content.push_str(&format!("\n@{} {}", tree.path(node), name));
+	}
+	for (level, record) in game.progress().records() {
+		content.push_str(&format!(
+			"\n*{} {} {}",
+			level.0, record.first_moves, record.best_moves
+		));
+	}
+	if snapshot {
+		for line in game.snapshot().serialize().lines() {
+			content.push_str("\n$");
+			content.push_str(line);
+		}
+	}
+	write_atomically(to, &content)?;
+	Ok(true)
+}
+
+/// Write the file through a temporary one, so that it's never left half
+/// written, and shift the backups. The file is linked as the first backup
+/// before the temporary one replaces it, so that there is always a file.
+pub fn write_atomically(path: &Path, content: &str) -> std::io::Result<()> {
+	return write_through_temporary(path, content, BACKUPS);
+}
+
+/// Like `write_atomically`, without backups: for the files the player keeps
+/// elsewhere, like a map under version control.
+pub fn write_without_backups(path: &Path, content: &str) -> std::io::Result<()> {
+	return write_through_temporary(path, content, 0);
+}
+
+fn write_through_temporary(path: &Path, content: &str, backups: usize) -> std::io::Result<()> {
+	let mut temporary = path.as_os_str().to_owned();
+	temporary.push(".tmp");
+	let mut file = std::fs::File::create(&temporary)?;
+	file.write_all(content.as_bytes())?;
+	file.sync_all()?;
+	drop(file);
+
+	for n in (1..backups).rev() {
+		let backup = backup_path(path, n);
+		if backup.exists() {
+			std::fs::rename(&backup, backup_path(path, n + 1))?;
+		}
+	}
+	if backups > 0 && path.exists() {
+		let backup = backup_path(path, 1);
+		if std::fs::hard_link(path, &backup).is_err() {
+			// Not every file system has hard links.
+			std::fs::copy(path, &backup)?;
+		}
+	}
+	std::fs::rename(&temporary, path)?;
+	#[cfg(unix)]
+	sync_directory(path)?;
+	return Ok(());
+}
+
+/// Make the renames in the file's directory durable.
+#[cfg(unix)]
+fn sync_directory(path: &Path) -> std::io::Result<()> {
+	let directory = match path.parent() {
+		Some(parent) if !parent.as_os_str().is_empty() => parent,
+		_ => Path::new("."),
+	};
+	return std::fs::File::open(directory)?.sync_all();
+}
+
+/// Read the header, up to the first empty line, and check it matches the game.
+/// Return it with the rest of the file.
+fn parse_header<'a>(
+	game: &crate::game::Game,
+	content: &'a str,
+) -> Result<(SaveInfo, &'a str), LoadError> {
+	let (header, body) = content.split_once("\n\n").unwrap_or((content, ""));
+	let mut info = SaveInfo {
+		format: 0,
+		game_version: None,
+		settings: Settings::new(),
+		backup: None,
+	};
+	let mut map_hash = None;
+	for line in header.lines() {
+		let invalid = || LoadError::InvalidHeader(line.to_owned());
+		let (key, value) = line.split_once(' ').ok_or_else(invalid)?;
+		match key {
+			"snowcrab" => {
+				let format = value.strip_prefix("save ").ok_or_else(invalid)?;
+				info.format = format.parse().map_err(|_| invalid())?;
+				if info.format > FORMAT_VERSION {
+					return Err(LoadError::UnsupportedFormat(info.format));
+				}
+			}
+			"game" => info.game_version = Some(value.to_owned()),
+			"map" => map_hash = Some(u64::from_str_radix(value, 16).map_err(|_| invalid())?),
+			"setting" => {
+				let (name, value) = value.split_once(' ').unwrap_or((value, ""));
+				info.settings.insert(name.to_owned(), value.to_owned());
+			}
+			// Left by a newer version, which still wrote a format this one knows.
+			_ => {}
+		}
+	}
+
+	match map_hash {
+		None => return Err(LoadError::InvalidHeader("missing map".to_owned())),
+		Some(hash) if hash != game.map_hash() => {
+			return Err(LoadError::MapMismatch {
+				game_version: info.game_version,
+			});
+		}
+		Some(_) => return Ok((info, body)),
+	}
+}
+
+/// Read a record of one of the `level_count` levels of the map.
+fn parse_record(record: &str, level_count: usize) -> Option<(LevelId, LevelRecord)> {
+	let mut numbers = record.split(' ').map(|n| n.parse::<usize>().ok());
+	let (level, first_moves, best_moves) = (numbers.next()??, numbers.next()??, numbers.next()??);
+	if level >= level_count {
+		return None;
+	}
+	let record = LevelRecord {
+		first_moves,
+		best_moves,
+	};
+	return Some((LevelId(level), record));
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::game::Game;
+	use std::path::PathBuf;
+
+	/// A fresh directory for the files of one test.
+	fn directory(test: &str) -> PathBuf {
+		let directory = std::env::temp_dir().join(format!("snowcrab-{test}-{}", std::process::id()));
+		let _ = std::fs::remove_dir_all(&directory);
+		std::fs::create_dir_all(&directory).unwrap();
+		return directory;
+	}
+
+	/// A level under room to walk around.
+	const MAP: &str = "P   \n    \n.sx \n";
+
+	/// A level completed by `STACK_MOVES`, the balls being stacked from above it.
+	const STACK_MAP: &str = "P    \n s.  \n m.  \n l.  \n";
+	const STACK_MOVES: &str = "DRDUURDRDLULD";
+
+	/// Play the actions, `-` being a rewind.
+	fn play(game: &mut Game, actions: &str) {
+		for c in actions.chars() {
+			match c {
+				'-' => assert!(game.rewind()),
+				_ => assert!(game.process_player_input(c.try_into().unwrap()), "{c} is blocked"),
+			}
+		}
+	}
+
+	#[test]
+	fn branches_bookmarks_and_settings_are_loaded_back() {
+		let mut game = Game::from_map_str(MAP).unwrap();
+		play(&mut game, "RR--");
+		game.set_bookmark("start");
+		play(&mut game, "DR");
+		game.set_bookmark("done");
+		play(&mut game, "-");
+		let path = directory("tree").join("save.txt");
+		let settings = Settings::from([("fps".to_owned(), "5".to_owned())]);
+		assert!(save(&game, &settings, &path).unwrap());
+
+		let mut loaded = Game::from_map_str(MAP).unwrap();
+		let info = load(&mut loaded, &path).unwrap();
+		assert_eq!(info.format, FORMAT_VERSION);
+		assert_eq!(info.game_version.as_deref(), Some(env!("CARGO_PKG_VERSION")));
+		assert_eq!(info.settings, settings);
+		assert_eq!(loaded.get_history(), game.get_history());
+		assert_eq!(loaded.get_redo(), game.get_redo());
+		let tree = loaded.history_tree();
+		assert_eq!(tree.serialize(), game.history_tree().serialize());
+		let bookmarks = |game: &Game| -> Vec<(String, String)> {
+			let tree = game.history_tree();
+			tree.bookmarks().map(|(name, node)| (name.to_owned(), tree.path(node))).collect()
+		};
+		assert_eq!(bookmarks(&loaded), bookmarks(&game));
+		std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+	}
+
+	#[test]
+	fn records_are_loaded_back() {
+		let mut game = Game::from_map_str(STACK_MAP).unwrap();
+		play(&mut game, STACK_MOVES);
+		assert!(game.progress().record(LevelId(0)).is_some());
+		let path = directory("records").join("save.txt");
+		assert!(save(&game, &Settings::new(), &path).unwrap());
+
+		let mut loaded = Game::from_map_str(STACK_MAP).unwrap();
+		load(&mut loaded, &path).unwrap();
+		assert_eq!(loaded.progress(), game.progress());
+		std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+	}
+
+	#[test]
+	fn record_of_an_unknown_level_is_refused() {
+		let mut game = Game::from_map_str(STACK_MAP).unwrap();
+		play(&mut game, STACK_MOVES);
+		let path = directory("unknown-record").join("save.txt");
+		assert!(save(&game, &Settings::new(), &path).unwrap());
+		let content = std::fs::read_to_string(&path).unwrap();
+		std::fs::write(&path, content.replace("*0 ", "*1 ")).unwrap();
+
+		let mut loaded = Game::from_map_str(STACK_MAP).unwrap();
+		let result = load(&mut loaded, &path);
+		assert!(matches!(result, Err(LoadError::InvalidRecord(ref r)) if r.starts_with("1 ")), "{result:?}");
+		std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+	}
+
+	#[test]
+	fn plain_history_loads_as_format_1() {
+		let path = directory("plain").join("save.txt");
+		std::fs::write(&path, "RDRR\n").unwrap();
+		let mut game = Game::from_map_str(MAP).unwrap();
+		let info = load(&mut game, &path).unwrap();
+		assert_eq!(info.format, 1);
+		assert_eq!(info.game_version, None);
+		assert_eq!(game.get_history(), "RDRR");
+		assert_eq!(game.player, (1, 3));
+
+		// Written in the current format from then on.
+		assert!(save(&game, &Settings::new(), &path).unwrap());
+		let mut reloaded = Game::from_map_str(MAP).unwrap();
+		assert_eq!(load(&mut reloaded, &path).unwrap().format, FORMAT_VERSION);
+		assert_eq!(reloaded.get_history(), "RDRR");
+		std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+	}
+
+	#[test]
+	fn redo_line_of_older_saves_is_loaded() {
+		let path = directory("legacy-redo").join("save.txt");
+		std::fs::write(&path, "R\n>R").unwrap();
+		let mut game = Game::from_map_str(MAP).unwrap();
+		load(&mut game, &path).unwrap();
+		assert_eq!(game.get_history(), "R");
+		assert!(game.redo());
+		assert_eq!(game.player, (0, 2));
+		std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+	}
+
+	#[test]
+	fn save_of_another_map_is_refused() {
+		let path = directory("mismatch").join("save.txt");
+		let mut game = Game::from_map_str(MAP).unwrap();
+		play(&mut game, "RDRR");
+		assert!(save(&game, &Settings::new(), &path).unwrap());
+		let content = std::fs::read_to_string(&path).unwrap();
+
+		let mut other = Game::from_map_str("P   \n    \n.xs \n").unwrap();
+		let result = load(&mut other, &path);
+		assert!(
+			matches!(result, Err(LoadError::MapMismatch { game_version: Some(ref v) }) if v == env!("CARGO_PKG_VERSION")),
+			"{result:?}"
+		);
+		assert_eq!(other.get_history(), "");
+		assert_eq!(std::fs::read_to_string(&path).unwrap(), content);
+		std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+	}
+
+	#[test]
+	fn snapshot_is_checked_only_when_replaying() {
+		let path = directory("snapshot").join("save.txt");
+		let mut game = Game::from_map_str(MAP).unwrap();
+		play(&mut game, "RDRR");
+		assert!(save_with_snapshot(&game, &Settings::new(), &path).unwrap());
+		let content = std::fs::read_to_string(&path).unwrap();
+		assert!(content.contains("\n$player 1 3\n"), "{content}");
+		std::fs::write(&path, content.replace("$player 1 3", "$player 1 2")).unwrap();
+
+		let mut loaded = Game::from_map_str(MAP).unwrap();
+		load(&mut loaded, &path).unwrap();
+		assert_eq!(loaded.get_history(), "RDRR");
+		assert_eq!(loaded.player, (1, 2));
+		let mut replayed = Game::from_map_str(MAP).unwrap();
+		let result = load_replaying(&mut replayed, &path);
+		assert!(matches!(result, Err(LoadError::Snapshot(SnapshotError::Mismatch))), "{result:?}");
+		std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+	}
+
+	#[test]
+	fn new_game_is_not_saved() {
+		let path = directory("new").join("save.txt");
+		assert!(!save(&Game::from_map_str(MAP).unwrap(), &Settings::new(), &path).unwrap());
+		assert!(!path.exists());
+		std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+	}
+
+	#[test]
+	fn writes_keep_the_previous_files_as_backups() {
+		let path = directory("backups").join("save.txt");
+		for content in ["1", "2", "3", "4", "5"] {
+			write_atomically(&path, content).unwrap();
+		}
+		let read = |path: &Path| std::fs::read_to_string(path).unwrap();
+		assert_eq!(read(&path), "5");
+		assert_eq!(read(&backup_path(&path, 1)), "4");
+		assert_eq!(read(&backup_path(&path, 2)), "3");
+		assert_eq!(read(&backup_path(&path, 3)), "2");
+		assert!(!backup_path(&path, 4).exists());
+		let mut temporary = path.as_os_str().to_owned();
+		temporary.push(".tmp");
+		assert!(!Path::new(&temporary).exists());
+		std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+	}
+
+	#[test]
+	fn writes_without_backups_leave_only_the_file() {
+		let directory = directory("no-backups");
+		let path = directory.join("map.txt");
+		write_without_backups(&path, "1").unwrap();
+		write_without_backups(&path, "2").unwrap();
+		assert_eq!(std::fs::read_to_string(&path).unwrap(), "2");
+		assert_eq!(std::fs::read_dir(&directory).unwrap().count(), 1);
+		std::fs::remove_dir_all(directory).unwrap();
 	}
 }
@@ -0,0 +1,140 @@
+//!
+//! Save the game now and then while it's played, so that a crash loses only
+//! the last moves.
+//!
+
+use std::io;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use super::Settings;
+use crate::game::Game;
+use crate::game::tree::NodeId;
+
+/// Saves the game every few actions or seconds, when it changed.
+#[derive(Clone, Debug)]
+pub struct Autosave {
+	path: PathBuf,
+	settings: Settings,
+	/// Whether to save a snapshot of the board too.
+	snapshot: bool,
+	/// Save after this many actions, if any.
+	every_actions: Option<usize>,
+	/// Save when the last save is this old, if any.
+	every: Option<Duration>,
+	/// Where the game was in the history at the last update, and the length
+	/// of the history then.
+	last: Option<(NodeId, usize)>,
+	/// The actions since the last save.
+	actions: usize,
+	last_save: Instant,
+}
+
+impl Autosave {
+	pub fn new(
+		path: PathBuf,
+		settings: Settings,
+		snapshot: bool,
+		every_actions: Option<usize>,
+		every: Option<Duration>,
+	) -> Autosave {
+		Autosave {
+			path,
+			settings,
+			snapshot,
+			every_actions,
+			every,
+			last: None,
+			actions: 0,
+			last_save: Instant::now(),
+		}
+	}
+
+	/// Call after each action, or now and then. Save if the game changed and
+	/// it's time to, and return what the save did then. The actions since the
+	/// last call are told by the length of the history, a jump to another
+	/// branch of the same length counting as one.
+	pub fn update(&mut self, game: &Game) -> Option<io::Result<bool>> {
+		let node = game.history_tree().current();
+		let len = game.get_history().len();
+		if let Some((previous, previous_len)) = self.last
+			&& previous != node
+		{
+			self.actions += len.abs_diff(previous_len).max(1);
+		}
+		self.last = Some((node, len));
+		if self.actions == 0 {
+			return None;
+		}
+		let enough_actions = self.every_actions.is_some_and(|n| self.actions >= n);
+		let long_enough = self.every.is_some_and(|d| self.last_save.elapsed() >= d);
+		if !enough_actions && !long_enough {
+			return None;
+		}
+		self.actions = 0;
+		self.last_save = Instant::now();
+		if self.snapshot {
+			return Some(super::save_with_snapshot(game, &self.settings, &self.path));
+		}
+		return Some(super::save(game, &self.settings, &self.path));
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/// A fresh save file for one test.
+	fn save_path(test: &str) -> PathBuf {
+		let directory = std::env::temp_dir().join(format!("snowcrab-autosave-{test}-{}", std::process::id()));
+		let _ = std::fs::remove_dir_all(&directory);
+		std::fs::create_dir_all(&directory).unwrap();
+		return directory.join("save.txt");
+	}
+
+	/// Play the actions, `-` being a rewind.
+	fn play(game: &mut Game, actions: &str) {
+		for c in actions.chars() {
+			match c {
+				'-' => assert!(game.rewind()),
+				_ => assert!(game.process_player_input(c.try_into().unwrap()), "{c} is blocked"),
+			}
+		}
+	}
+
+	const MAP: &str = "P   \n    \n    \n";
+
+	#[test]
+	fn actions_between_updates_all_count() {
+		let path = save_path("actions");
+		let mut game = Game::from_map_str(MAP).unwrap();
+		let mut autosave = Autosave::new(path.clone(), Settings::new(), false, Some(3), None);
+		assert!(autosave.update(&game).is_none());
+		play(&mut game, "RR");
+		assert!(autosave.update(&game).is_none());
+		assert!(!path.exists());
+		play(&mut game, "D");
+		assert!(autosave.update(&game).unwrap().unwrap());
+		assert!(path.exists());
+		// Nothing changed since.
+		assert!(autosave.update(&game).is_none());
+		// Rewinds are actions too.
+		play(&mut game, "RD");
+		assert!(autosave.update(&game).is_none());
+		play(&mut game, "-");
+		assert!(autosave.update(&game).unwrap().unwrap());
+	}
+
+	#[test]
+	fn saves_when_the_last_save_is_old_enough() {
+		let path = save_path("every");
+		let mut game = Game::from_map_str(MAP).unwrap();
+		let mut autosave = Autosave::new(path.clone(), Settings::new(), true, None, Some(Duration::ZERO));
+		assert!(autosave.update(&game).is_none());
+		play(&mut game, "R");
+		assert!(autosave.update(&game).unwrap().unwrap());
+		let mut loaded = Game::from_map_str(MAP).unwrap();
+		crate::save::load(&mut loaded, &path).unwrap();
+		assert_eq!(loaded.get_history(), game.get_history());
+	}
+}
@@ -0,0 +1,195 @@
+//!
+//! Named save slots, one file each in a directory, so that several players
+//! can keep their own game on the same machine. By default the directory is
+//! `snowcrab/slots` in the XDG data directory, `~/.local/share` if unset.
+//!
+
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// The slot used when the player doesn't choose one.
+pub const DEFAULT_SLOT: &str = "default";
+const EXTENSION: &str = "save";
+
+/// The directory of the save slots.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Slots {
+	dir: PathBuf,
+}
+
+impl Slots {
+	/// The slots in the default directory, if the data directory is known.
+	pub fn open_default() -> io::Result<Slots> {
+		let data_dir = std::env::var_os("XDG_DATA_HOME")
+			.filter(|dir| Path::new(dir).is_absolute())
+			.map(PathBuf::from)
+			.or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".local/share")))
+			.or_else(|| std::env::var_os("APPDATA").map(PathBuf::from))
+			.ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no data directory"))?;
+		return Slots::open(data_dir.join("snowcrab").join("slots"));
+	}
+
+	/// The slots in `dir`, created if needed.
+	pub fn open<P: Into<PathBuf>>(dir: P) -> io::Result<Slots> {
+		let dir = dir.into();
+		std::fs::create_dir_all(&dir)?;
+		return Ok(Slots { dir });
+	}
+
+	pub fn dir(&self) -> &Path {
+		&self.dir
+	}
+
+	/// The names of the existing slots, in alphabetical order.
+	pub fn list(&self) -> io::Result<Vec<String>> {
+		let mut names = Vec::new();
+		for entry in std::fs::read_dir(&self.dir)? {
+			let path = entry?.path();
+			if path.extension().is_some_and(|ext| ext == EXTENSION)
+				&& let Some(name) = path.file_stem().and_then(|name| name.to_str())
+			{
+				names.push(name.to_owned());
+			}
+		}
+		names.sort();
+		return Ok(names);
+	}
+
+	pub fn exists(&self, name: &str) -> bool {
+		self.path(name).is_ok_and(|path| path.exists())
+	}
+
+	/// The save file of the slot, which may not exist yet.
+	pub fn path(&self, name: &str) -> io::Result<PathBuf> {
+		check_name(name)?;
+		return Ok(self.dir.join(format!("{name}.{EXTENSION}")));
+	}
+
+	/// Copy the slot `from` to a new slot `to`.
+	pub fn copy(&self, from: &str, to: &str) -> io::Result<()> {
+		let (from, to) = (self.existing(from)?, self.new_slot(to)?);
+		return std::fs::copy(from, to).map(|_| ());
+	}
+
+	/// Rename the slot `from` to `to`, which must not exist, with its backups.
+	pub fn rename(&self, from: &str, to: &str) -> io::Result<()> {
+		let (from, to) = (self.existing(from)?, self.new_slot(to)?);
+		for n in 1..=super::BACKUPS {
+			let backup = super::backup_path(&from, n);
+			if backup.exists() {
+				std::fs::rename(backup, super::backup_path(&to, n))?;
+			}
+		}
+		return std::fs::rename(from, to);
+	}
+
+	/// Delete the slot and its backups.
+	pub fn delete(&self, name: &str) -> io::Result<()> {
+		let path = self.existing(name)?;
+		for n in 1..=super::BACKUPS {
+			let backup = super::backup_path(&path, n);
+			if backup.exists() {
+				std::fs::remove_file(backup)?;
+			}
+		}
+		return std::fs::remove_file(path);
+	}
+
+	fn existing(&self, name: &str) -> io::Result<PathBuf> {
+		let path = self.path(name)?;
+		if !path.exists() {
+			let message = format!("there is no slot {name:?}");
+			return Err(io::Error::new(io::ErrorKind::NotFound, message));
+		}
+		return Ok(path);
+	}
+
+	fn new_slot(&self, name: &str) -> io::Result<PathBuf> {
+		let path = self.path(name)?;
+		if path.exists() {
+			let message = format!("the slot {name:?} already exists");
+			return Err(io::Error::new(io::ErrorKind::AlreadyExists, message));
+		}
+		return Ok(path);
+	}
+}
+
+/// A slot's name is its file's name: it can't be empty, hidden or contain a path.
+fn check_name(name: &str) -> io::Result<()> {
+	let valid = !name.is_empty()
+		&& !name.starts_with('.')
+		&& !name.contains(['/', '\\', ':'])
+		&& !name.chars().any(char::is_control);
+	if !valid {
+		let message = format!("invalid slot name {name:?}");
+		return Err(io::Error::new(io::ErrorKind::InvalidInput, message));
+	}
+	return Ok(());
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/// Slots in a fresh directory for one test.
+	fn slots(test: &str) -> Slots {
+		let directory = std::env::temp_dir().join(format!("snowcrab-slots-{test}-{}", std::process::id()));
+		let _ = std::fs::remove_dir_all(&directory);
+		return Slots::open(directory).unwrap();
+	}
+
+	fn write(slots: &Slots, name: &str, content: &str) {
+		std::fs::write(slots.path(name).unwrap(), content).unwrap();
+	}
+
+	#[test]
+	fn only_save_files_are_listed() {
+		let slots = slots("list");
+		write(&slots, "bob", "");
+		write(&slots, "alice", "");
+		std::fs::write(slots.dir().join("notes.txt"), "").unwrap();
+		assert_eq!(slots.list().unwrap(), ["alice", "bob"]);
+		assert!(slots.exists("bob"));
+		assert!(!slots.exists("carol"));
+	}
+
+	#[test]
+	fn names_cannot_be_paths() {
+		let slots = slots("names");
+		for name in ["", ".hidden", "a/b", "a\\b", "c:", "a\nb"] {
+			let error = slots.path(name).unwrap_err();
+			assert_eq!(error.kind(), io::ErrorKind::InvalidInput, "{name:?}");
+		}
+		assert_eq!(slots.path("bob").unwrap(), slots.dir().join("bob.save"));
+	}
+
+	#[test]
+	fn copy_and_rename_make_new_slots() {
+		let slots = slots("copy");
+		write(&slots, "alice", "a");
+		write(&slots, "bob", "b");
+		assert_eq!(slots.copy("alice", "bob").unwrap_err().kind(), io::ErrorKind::AlreadyExists);
+		assert_eq!(slots.copy("carol", "dave").unwrap_err().kind(), io::ErrorKind::NotFound);
+		slots.copy("alice", "carol").unwrap();
+		assert_eq!(std::fs::read_to_string(slots.path("carol").unwrap()).unwrap(), "a");
+
+		std::fs::write(crate::save::backup_path(slots.path("bob").unwrap(), 1), "old b").unwrap();
+		slots.rename("bob", "dave").unwrap();
+		assert_eq!(slots.list().unwrap(), ["alice", "carol", "dave"]);
+		assert_eq!(std::fs::read_to_string(slots.path("dave").unwrap()).unwrap(), "b");
+		let backup = crate::save::backup_path(slots.path("dave").unwrap(), 1);
+		assert_eq!(std::fs::read_to_string(backup).unwrap(), "old b");
+	}
+
+	#[test]
+	fn delete_removes_the_backups() {
+		let slots = slots("delete");
+		write(&slots, "alice", "a");
+		let backup = crate::save::backup_path(slots.path("alice").unwrap(), 1);
+		std::fs::write(&backup, "old a").unwrap();
+		slots.delete("alice").unwrap();
+		assert!(!slots.exists("alice"));
+		assert!(!backup.exists());
+		assert_eq!(slots.delete("alice").unwrap_err().kind(), io::ErrorKind::NotFound);
+	}
+}
@@ -0,0 +1,285 @@
+//!
+//! Play in a terminal, for the machines without a display. Each tile is drawn as
+//! a cell two characters wide, with the colors of `view::draw_one_tile`.
+//!
+
+use std::io::{self, Stdout, Write};
+use std::time::Duration;
+
+use crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use crossterm::style::{Color, Print, ResetColor, SetBackgroundColor, SetForegroundColor};
+use crossterm::terminal::{self, ClearType};
+use crossterm::{cursor, execute, queue};
+
+use snowcrab::game::{Direction, Game, SnowBall, Tile};
+use snowcrab::save::autosave::Autosave;
+use snowcrab::solver;
+
+use crate::palette;
+
+/// Lines under the map, for the counters and the messages.
+const STATUS_LINES: u16 = 2;
+/// How close to the edge of the view the player can get before the view moves.
+const SCROLL_MARGIN: usize = 2;
+/// The limits of a whole hint, during which the terminal does not respond.
+const HINT_LIMITS: solver::Limits = solver::Limits {
+	max_nodes: 2_000_000,
+	max_duration: Some(Duration::from_secs(3)),
+};
+
+/// Shown at first, there is no room for more.
+const CONTROLS: &str =
+	"Z/Q/S/D or arrows: move. E/R: rewind. F: redo. T: reset. H: hint. Space: recenter. ESC: quit.";
+
+/// Play until the player quits. `message` is shown first, or the controls.
+pub fn play(
+	game: &mut Game,
+	message: Option<String>,
+	mut autosave: Option<Autosave>,
+) -> io::Result<()> {
+	let mut screen = Screen::enter()?;
+	let mut message = message.unwrap_or_else(|| CONTROLS.to_owned());
+	let mut view = View::default();
+	view.center_around_player(game, terminal::size()?);
+
+	loop {
+		let size = terminal::size()?;
+		view.follow_player(game, size);
+		screen.draw(game, view, size, &message)?;
+
+		// Wake up now and then for the autosave.
+		if !event::poll(Duration::from_secs(1))? {
+			if let Some(text) = crate::autosave_message(&mut autosave, game) {
+				message = text;
+			}
+			continue;
+		}
+		let key = match event::read()? {
+			Event::Key(key) if key.kind == KeyEventKind::Press => key,
+			// Resizing, for instance, only needs a redraw.
+			_ => continue,
+		};
+		message.clear();
+		match key.code {
+			KeyCode::Esc => break,
+			KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => break,
+			KeyCode::Up => _ = game.process_player_input(Direction::Up),
+			KeyCode::Left => _ = game.process_player_input(Direction::Left),
+			KeyCode::Down => _ = game.process_player_input(Direction::Down),
+			KeyCode::Right => _ = game.process_player_input(Direction::Right),
+			KeyCode::Char(c) => match c.to_ascii_lowercase() {
+				'z' => _ = game.process_player_input(Direction::Up),
+				'q' => _ = game.process_player_input(Direction::Left),
+				's' => _ = game.process_player_input(Direction::Down),
+				'd' => _ = game.process_player_input(Direction::Right),
+				'e' | 'r' => _ = game.rewind(),
+				'f' => _ = game.redo(),
+				't' => _ = game.reset_current_level(),
+				'h' => message = crate::hint_message(&game.hint(&HINT_LIMITS)),
+				' ' => view.center_around_player(game, size),
+				_ => {}
+			},
+			_ => {}
+		}
+		for event in game.take_events() {
+			if let Some(text) = crate::event_message(game, &event) {
+				message = text;
+			}
+		}
+		if let Some(text) = crate::autosave_message(&mut autosave, game) {
+			message = text;
+		}
+		if message.is_empty()
+			&& let Some(deadlock) = game.current_level_deadlocks().first()
+		{
+			message = format!("This level cannot be completed anymore, {deadlock}.");
+		}
+	}
+	return Ok(());
+}
+
+/// The tile at the top-left corner of the view.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+struct View {
+	base_x: usize,
+	base_y: usize,
+}
+
+impl View {
+	/// The number of tiles the terminal can show, vertically and horizontally.
+	fn len((columns, rows): (u16, u16)) -> (usize, usize) {
+		let len_x = rows.saturating_sub(STATUS_LINES) as usize;
+		let len_y = columns as usize / 2;
+		return (len_x, len_y);
+	}
+
+	fn center_around_player(&mut self, game: &Game, size: (u16, u16)) {
+		let (len_x, len_y) = View::len(size);
+		self.base_x = game
+			.player
+			.0
+			.saturating_sub(len_x / 2)
+			.min(game.size_x().saturating_sub(len_x));
+		self.base_y = game
+			.player
+			.1
+			.saturating_sub(len_y / 2)
+			.min(game.size_y().saturating_sub(len_y));
+	}
+
+	/// Recenter the view if the player gets close to its edges.
+	fn follow_player(&mut self, game: &Game, size: (u16, u16)) {
+		let (len_x, len_y) = View::len(size);
+		let (px, py) = game.player;
+		let near_edge = |p: usize, base: usize, len: usize| {
+			let margin = SCROLL_MARGIN.min(len / 4);
+			p < base + margin || p + margin >= base + len
+		};
+		if near_edge(px, self.base_x, len_x) || near_edge(py, self.base_y, len_y) {
+			self.center_around_player(game, size);
+		}
+	}
+}
+
+/// The terminal in raw mode, on the alternate screen. Dropping it restores the terminal.
+struct Screen {
+	out: Stdout,
+}
+
+impl Screen {
+	fn enter() -> io::Result<Screen> {
+		let mut out = io::stdout();
+		terminal::enable_raw_mode()?;
+		execute!(out, terminal::EnterAlternateScreen, cursor::Hide)?;
+		return Ok(Screen { out });
+	}
+
+	fn draw(&mut self, game: &Game, view: View, size: (u16, u16), message: &str) -> io::Result<()> {
+		let (len_x, len_y) = View::len(size);
+		for row in 0..len_x {
+			queue!(self.out, cursor::MoveTo(0, row as u16))?;
+			for column in 0..len_y {
+				let (x, y) = (view.base_x + row, view.base_y + column);
+				if x >= game.size_x() || y >= game.size_y() {
+					queue!(self.out, ResetColor, Print("  "))?;
+					continue;
+				}
+				let (background, foreground, text) = cell(game, x, y);
+				queue!(
+					self.out,
+					SetBackgroundColor(background),
+					SetForegroundColor(foreground),
+					Print(text)
+				)?;
+			}
+			queue!(self.out, ResetColor, terminal::Clear(ClearType::UntilNewLine))?;
+		}
+
+		let level = match game.current_level() {
+			Some(level) if game.is_level_complete(level.id) => format!("{} (completed)", level.title()),
+			Some(level) => level.title(),
+			None => "Outside the levels".to_owned(),
+		};
+		let counters = format!(
+			"{level} | Moves: {} | Snowmen: {}/{} | Rewind depth: {}",
+			game.get_history().len(),
+			game.snowmen_count(),
+			game.levels().required_snowmen(),
+			game.rewind_depth()
+		);
+		queue!(
+			self.out,
+			cursor::MoveTo(0, len_x as u16),
+			Print(counters),
+			terminal::Clear(ClearType::UntilNewLine),
+			cursor::MoveToNextLine(1),
+			Print(message),
+			terminal::Clear(ClearType::FromCursorDown)
+		)?;
+		return self.out.flush();
+	}
+}
+
+impl Drop for Screen {
+	fn drop(&mut self) {
+		// Nothing better to do if the terminal cannot be restored.
+		let _ = execute!(self.out, ResetColor, cursor::Show, terminal::LeaveAlternateScreen);
+		let _ = terminal::disable_raw_mode();
+	}
+}
+
+/// The background color, the foreground color and the two characters of a tile.
+fn cell(game: &Game, x: usize, y: usize) -> (Color, Color, &'static str) {
+	let background = rgb(palette::tile_color(game.tiles[x][y]));
+	if game.player == (x, y) {
+		return (rgb(palette::PLAYER_COLOR), Color::White, "@@");
+	}
+	if let Tile::Portal(number) = game.tiles[x][y] {
+		let color = palette::portal_color(number, game.portals().exit((x, y)).is_some());
+		return (background, rgb(color), "()");
+	}
+	// The top ball on the left, the bottom one on the right.
+	let text = match (game.snowballs[x][y], game.tiles[x][y]) {
+		(Some(SnowBall::Small), _) => " ·",
+		(Some(SnowBall::Medium), _) => " o",
+		(Some(SnowBall::Large), _) => " O",
+		(Some(SnowBall::SmallOnMedium), _) => "·o",
+		(Some(SnowBall::SmallOnLarge), _) => "·O",
+		(Some(SnowBall::MediumOnLarge), _) => "oO",
+		(Some(SnowBall::Snowman), _) => "&&",
+		(None, Tile::Hedge) => "##",
+		(None, Tile::Tree) => "^^",
+		(None, Tile::Obstacle) => "[]",
+		(None, _) => "  ",
+	};
+	return (background, Color::Black, text);
+}
+
+fn rgb([r, g, b, _]: [f32; 4]) -> Color {
+	let channel = |c: f32| (c * 255.0).round() as u8;
+	return Color::Rgb {
+		r: channel(r),
+		g: channel(g),
+		b: channel(b),
+	};
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn cells_show_the_player_and_the_stacks() {
+		let game = Game::from_map_str("Px\n#X\n").unwrap();
+		assert_eq!(cell(&game, 0, 0).2, "@@");
+		assert_eq!(cell(&game, 0, 1), (rgb(palette::tile_color(Tile::Dirt)), Color::Black, "·o"));
+		assert_eq!(cell(&game, 1, 0).2, "##");
+		assert_eq!(cell(&game, 1, 1).0, rgb(palette::tile_color(Tile::Snow)));
+	}
+
+	#[test]
+	fn view_follows_the_player_near_its_edges() {
+		// Ten columns and twelve rows: ten tiles high and five wide.
+		let size = (10, 12);
+		let row = " ".repeat(20);
+		let map = format!("{row}\n{row}\n{row}\n{row}\n{row}\nP{}\n", &row[1..]);
+		let mut game = Game::from_map_str(&map).unwrap();
+		let mut view = View::default();
+		view.center_around_player(&game, size);
+		assert_eq!(view, View::default());
+		for _ in 0..3 {
+			game.process_player_input(Direction::Right);
+			view.follow_player(&game, size);
+		}
+		assert_eq!(view, View::default());
+		game.process_player_input(Direction::Right);
+		view.follow_player(&game, size);
+		assert_eq!(view, View { base_x: 0, base_y: 2 });
+		// Not past the edge of the map.
+		for _ in 0..15 {
+			game.process_player_input(Direction::Right);
+		}
+		view.center_around_player(&game, size);
+		assert_eq!(view, View { base_x: 0, base_y: 15 });
+	}
+}
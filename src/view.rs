@@ -1,5 +1,6 @@
 //!
-//!
+//! Draw the game with geometrical shapes, in the piston window or on any other
+//! backend of piston's graphics, as the image export does.
 //!
 
 pub const TILE_SIDE: f64 = 64.0;
@@ -26,6 +27,14 @@ const BALL_LARGE: [f64; 4] = [
 	TILE_SIDE * RADIUS_LARGE,
 	TILE_SIDE * RADIUS_LARGE,
 ];
+// portal
+const PORTAL_RECTANGLE: [f64; 4] = [
+	TILE_SIDE * 0.125,
+	TILE_SIDE * 0.125,
+	TILE_SIDE * 0.75,
+	TILE_SIDE * 0.75,
+];
+const PORTAL_BORDER: f64 = TILE_SIDE * 0.0625;
 // the player
 const PLAYER_RECTANGLE: [f64; 4] = [
 	TILE_SIDE / 4.0,
@@ -43,15 +52,24 @@ const SNOWBALL_DRAWER: Ellipse = Ellipse {
 	resolution: 128,
 };
 
-use piston_window::{clear, ellipse, line_from_to, rectangle, Context, Ellipse, G2d, Transformed};
+use graphics::{clear, ellipse, line_from_to, rectangle, Context, Ellipse, Graphics, Transformed};
+
+use snowcrab::game;
 
-use crate::game;
+use crate::palette;
 
-pub fn draw_all(vp: Viewport, game: &game::Game, context: Context, graphics: &mut G2d) {
+#[cfg(feature = "gui")]
+mod hud;
+#[cfg(feature = "gui")]
+pub use hud::{Messages, draw_deadlocks, draw_hint, draw_hud, draw_replay_status};
+#[cfg(feature = "editor")]
+pub use hud::{draw_cursor, draw_editor_hud};
+
+pub fn draw_all<G: Graphics>(vp: Viewport, game: &game::Game, context: Context, graphics: &mut G) {
 	clear([0.125, 0.125, 0.125, 1.0], graphics);
 
-	let max_x = usize::min(vp.base_x + vp.len_x + 1, game::SIZE_X);
-	let max_y = usize::min(vp.base_y + vp.len_y + 1, game::SIZE_Y);
+	let max_x = usize::min(vp.base_x + vp.len_x + 1, game.size_x());
+	let max_y = usize::min(vp.base_y + vp.len_y + 1, game.size_y());
 
 	for x in (vp.base_x)..max_x {
 		for y in (vp.base_y)..max_y {
@@ -66,31 +84,33 @@ pub fn draw_all(vp: Viewport, game: &game::Game, context: Context, graphics: &mu
 }
 
 /// Draw all but the player.
-fn draw_one_tile(
+fn draw_one_tile<G: Graphics>(
 	x: usize,
 	y: usize,
 	vp: Viewport,
 	game: &game::Game,
 	context: Context,
-	graphics: &mut G2d,
+	graphics: &mut G,
 ) {
 	let tx = (x - vp.base_x) as f64 * TILE_SIDE;
 	let ty = (y - vp.base_y) as f64 * TILE_SIDE;
 	// Background tile.
-	let color = match game.tiles[x][y] {
-		game::Tile::Empty => [0.375, 0.375, 0.375, 1.0],
-		game::Tile::Dirt => [0.625, 0.4375, 0.125, 1.0],
-		game::Tile::Snow => [0.8125, 0.8125, 0.875, 1.0],
-		game::Tile::Hedge => [0.0625, 0.375, 0.0, 1.0],
-		game::Tile::Tree => [0.125, 0.75, 0.25, 1.0],
-		game::Tile::Obstacle => [0.375, 0.25, 0.375, 1.0],
-	};
 	rectangle(
-		color,
+		palette::tile_color(game.tiles[x][y]),
 		TILE_RECTANGLE,
 		context.transform.trans(ty, tx),
 		graphics,
 	);
+	// The ring of the portals.
+	if let game::Tile::Portal(number) = game.tiles[x][y] {
+		let color = palette::portal_color(number, game.portals().exit((x, y)).is_some());
+		Ellipse::new_border(color, PORTAL_BORDER).draw(
+			PORTAL_RECTANGLE,
+			&context.draw_state,
+			context.transform.trans(ty, tx),
+			graphics,
+		);
+	}
 	// Foreground snow balls.
 	if let Some(snowball) = game.snowballs[x][y] {
 		let rect = match snowball {
@@ -162,11 +182,11 @@ fn draw_one_tile(
 	}
 }
 
-fn draw_player(x: usize, y: usize, context: Context, graphics: &mut G2d) {
+fn draw_player<G: Graphics>(x: usize, y: usize, context: Context, graphics: &mut G) {
 	let tx = x as f64 * TILE_SIDE;
 	let ty = y as f64 * TILE_SIDE;
 	rectangle(
-		[0.75, 0.0625, 0.125, 1.0],
+		palette::PLAYER_COLOR,
 		PLAYER_RECTANGLE,
 		context.transform.trans(ty, tx),
 		graphics,
@@ -183,36 +203,3 @@ pub struct Viewport {
 	pub len_x: usize,
 	pub len_y: usize,
 }
-
-impl Viewport {
-	pub fn new(game: &game::Game, window_size: (usize, usize)) -> Viewport {
-		let len_x = (window_size.0 as f64 / TILE_SIDE) as usize;
-		let len_y = (window_size.1 as f64 / TILE_SIDE) as usize;
-		let base_x = game.player.0.saturating_sub(len_x / 2);
-		let base_y = game.player.1.saturating_sub(len_y / 2);
-		Viewport {
-			base_x,
-			base_y,
-			len_x,
-			len_y,
-		}
-	}
-
-	pub fn center_around_player(&mut self, game: &game::Game) {
-		self.base_x = game
-			.player
-			.0
-			.saturating_sub(self.len_x / 2)
-			.min(game::SIZE_X - self.len_x);
-		self.base_y = game
-			.player
-			.1
-			.saturating_sub(self.len_y / 2)
-			.min(game::SIZE_Y - self.len_y);
-	}
-
-	pub fn resize(&mut self, args: piston_window::ResizeArgs) {
-		self.len_x = (args.window_size[1] / TILE_SIDE) as usize;
-		self.len_y = (args.window_size[0] / TILE_SIDE) as usize;
-	}
-}
@@ -0,0 +1,311 @@
+//!
+//! What only the window draws over the board: the hint, the deadlocks, the
+//! counters and the messages. And how its view follows the player.
+//!
+
+// the hint
+const HINT_COLOR: [f32; 4] = [1.0, 0.875, 0.25, 0.375];
+// the deadlocks
+const DEADLOCK_COLOR: [f32; 4] = [0.875, 0.125, 0.125, 1.0];
+// the tile under the mouse in the editor
+#[cfg(feature = "editor")]
+const CURSOR_COLOR: [f32; 4] = [1.0, 1.0, 1.0, 1.0];
+// the heads-up display
+const HUD_FONT_SIZE: u32 = 16;
+const HUD_LINE_HEIGHT: f64 = 22.0;
+const HUD_MARGIN: f64 = 8.0;
+const HUD_BACKGROUND: [f32; 4] = [0.0, 0.0, 0.0, 0.625];
+const HUD_TEXT_COLOR: [f32; 4] = [0.9375, 0.9375, 0.9375, 1.0];
+/// How long a message stays on screen, in seconds. It fades out during the last one.
+const MESSAGE_DURATION: f64 = 5.0;
+const MAX_MESSAGES: usize = 6;
+
+use std::collections::VecDeque;
+
+use piston_window::graphics::character::CharacterCache;
+use piston_window::graphics::{line_from_to, rectangle, text, Context, Rectangle, Transformed};
+use piston_window::wgpu_graphics::WgpuGraphics as G2d;
+use piston_window::Glyphs;
+
+use snowcrab::game;
+
+use super::{Viewport, TILE_RECTANGLE, TILE_SIDE};
+
+/// Highlight the tile the hint suggests moving to, with a line from the player.
+pub fn draw_hint(
+	vp: Viewport,
+	game: &game::Game,
+	target: (usize, usize),
+	context: Context,
+	graphics: &mut G2d,
+) {
+	let (x, y) = target;
+	if x < vp.base_x || y < vp.base_y || game.player.0 < vp.base_x || game.player.1 < vp.base_y {
+		return;
+	}
+	let tx = (x - vp.base_x) as f64 * TILE_SIDE;
+	let ty = (y - vp.base_y) as f64 * TILE_SIDE;
+	let px = (game.player.0 - vp.base_x) as f64 * TILE_SIDE;
+	let py = (game.player.1 - vp.base_y) as f64 * TILE_SIDE;
+	rectangle(
+		HINT_COLOR,
+		TILE_RECTANGLE,
+		context.transform.trans(ty, tx),
+		graphics,
+	);
+	line_from_to(
+		[1.0, 0.875, 0.25, 1.0],
+		3.0,
+		[py + TILE_SIDE * 0.5, px + TILE_SIDE * 0.5],
+		[ty + TILE_SIDE * 0.5, tx + TILE_SIDE * 0.5],
+		context.transform,
+		graphics,
+	);
+}
+
+/// Outline in red the snowballs involved in a deadlock, and the whole
+/// view if some deadlock concerns the level as a whole.
+pub fn draw_deadlocks(
+	vp: Viewport,
+	deadlocks: &[game::deadlock::Deadlock],
+	context: Context,
+	graphics: &mut G2d,
+) {
+	let border = Rectangle::new_border(DEADLOCK_COLOR, 3.0);
+	for deadlock in deadlocks {
+		if deadlock.tiles().is_empty() {
+			let [width, height] = context.get_view_size();
+			border.draw(
+				[0.0, 0.0, width, height],
+				&context.draw_state,
+				context.transform,
+				graphics,
+			);
+		}
+		for &(x, y) in deadlock.tiles() {
+			if x < vp.base_x || y < vp.base_y {
+				continue;
+			}
+			let tx = (x - vp.base_x) as f64 * TILE_SIDE;
+			let ty = (y - vp.base_y) as f64 * TILE_SIDE;
+			border.draw(
+				TILE_RECTANGLE,
+				&context.draw_state,
+				context.transform.trans(ty, tx),
+				graphics,
+			);
+		}
+	}
+}
+
+/// Draw the game's counters in the top-left corner of the window,
+/// and the recent messages in the bottom-left one.
+pub fn draw_hud(
+	game: &game::Game,
+	messages: &Messages,
+	glyphs: &mut Glyphs,
+	context: Context,
+	graphics: &mut G2d,
+) {
+	let level = match game.current_level() {
+		Some(level) if game.is_level_complete(level.id) => format!("{} (completed)", level.title()),
+		Some(level) => level.title(),
+		None => "Outside the levels".to_owned(),
+	};
+	let counters = [
+		level,
+		format!("Moves: {}", game.get_history().len()),
+		format!(
+			"Snowmen: {}/{}",
+			game.snowmen_count(),
+			game.levels().required_snowmen()
+		),
+		format!("Rewind depth: {}", game.rewind_depth()),
+	];
+	draw_text_box(&counters, 1.0, HUD_MARGIN, glyphs, context, graphics);
+	draw_messages(messages, glyphs, context, graphics);
+}
+
+/// Draw the state of the editor in the top-left corner of the window, and
+/// the recent messages in the bottom-left one.
+#[cfg(feature = "editor")]
+pub fn draw_editor_hud<S: AsRef<str>>(
+	lines: &[S],
+	messages: &Messages,
+	glyphs: &mut Glyphs,
+	context: Context,
+	graphics: &mut G2d,
+) {
+	draw_text_box(lines, 1.0, HUD_MARGIN, glyphs, context, graphics);
+	draw_messages(messages, glyphs, context, graphics);
+}
+
+/// Outline the tile under the mouse in the editor.
+#[cfg(feature = "editor")]
+pub fn draw_cursor(vp: Viewport, (x, y): (usize, usize), context: Context, graphics: &mut G2d) {
+	if x < vp.base_x || y < vp.base_y {
+		return;
+	}
+	let tx = (x - vp.base_x) as f64 * TILE_SIDE;
+	let ty = (y - vp.base_y) as f64 * TILE_SIDE;
+	Rectangle::new_border(CURSOR_COLOR, 2.0).draw(
+		TILE_RECTANGLE,
+		&context.draw_state,
+		context.transform.trans(ty, tx),
+		graphics,
+	);
+}
+
+/// Draw the recent messages in the bottom-left corner of the window.
+fn draw_messages(messages: &Messages, glyphs: &mut Glyphs, context: Context, graphics: &mut G2d) {
+	let [_, height] = context.get_view_size();
+	let lines: Vec<&str> = messages.lines.iter().map(|(text, _)| text.as_str()).collect();
+	if lines.is_empty() {
+		return;
+	}
+	// The box fades out with its most recent message.
+	let age = messages.lines.back().map_or(0.0, |&(_, age)| age);
+	let opacity = (MESSAGE_DURATION - age).clamp(0.0, 1.0) as f32;
+	let top = height - HUD_MARGIN - (lines.len() as f64 * HUD_LINE_HEIGHT + HUD_MARGIN);
+	draw_text_box(&lines, opacity, top, glyphs, context, graphics);
+}
+
+/// Draw the state of a replay under the game's counters.
+pub fn draw_replay_status<S: AsRef<str>>(
+	lines: &[S],
+	glyphs: &mut Glyphs,
+	context: Context,
+	graphics: &mut G2d,
+) {
+	// Below the four lines of counters.
+	let top = 3.0 * HUD_MARGIN + 4.0 * HUD_LINE_HEIGHT;
+	draw_text_box(lines, 1.0, top, glyphs, context, graphics);
+}
+
+/// Draw lines of text on a dark background, at the left of the window.
+fn draw_text_box<S: AsRef<str>>(
+	lines: &[S],
+	opacity: f32,
+	top: f64,
+	glyphs: &mut Glyphs,
+	context: Context,
+	graphics: &mut G2d,
+) {
+	let width = lines
+		.iter()
+		.map(|line| glyphs.width(HUD_FONT_SIZE, line.as_ref()).unwrap_or(0.0))
+		.fold(0.0, f64::max);
+	let [r, g, b, a] = HUD_BACKGROUND;
+	rectangle(
+		[r, g, b, a * opacity],
+		[
+			HUD_MARGIN,
+			top,
+			width + 2.0 * HUD_MARGIN,
+			lines.len() as f64 * HUD_LINE_HEIGHT + HUD_MARGIN,
+		],
+		context.transform,
+		graphics,
+	);
+	let [r, g, b, a] = HUD_TEXT_COLOR;
+	let writer = text::Text::new_color([r, g, b, a * opacity], HUD_FONT_SIZE);
+	for (i, line) in lines.iter().enumerate() {
+		let baseline = top + (i + 1) as f64 * HUD_LINE_HEIGHT;
+		// A glyph that cannot be rendered is not worth stopping the game for.
+		let _ = writer.draw(
+			line.as_ref(),
+			glyphs,
+			&context.draw_state,
+			context.transform.trans(2.0 * HUD_MARGIN, baseline),
+			graphics,
+		);
+	}
+}
+
+/// The messages for the player, shown for a few seconds over the map.
+#[derive(Clone, Debug, Default)]
+pub struct Messages {
+	/// The text of each message and for how long it has been shown, the most recent last.
+	lines: VecDeque<(String, f64)>,
+}
+
+impl Messages {
+	pub fn push<S: Into<String>>(&mut self, text: S) {
+		if self.lines.len() >= MAX_MESSAGES {
+			self.lines.pop_front();
+		}
+		self.lines.push_back((text.into(), 0.0));
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.lines.is_empty()
+	}
+
+	/// Age the messages by `dt` seconds and forget the old ones.
+	pub fn update(&mut self, dt: f64) {
+		for (_, age) in self.lines.iter_mut() {
+			*age += dt;
+		}
+		self.lines.retain(|&(_, age)| age < MESSAGE_DURATION);
+	}
+}
+
+impl Viewport {
+	pub fn new(game: &game::Game, window_size: (usize, usize)) -> Viewport {
+		let len_x = (window_size.0 as f64 / TILE_SIDE) as usize;
+		let len_y = (window_size.1 as f64 / TILE_SIDE) as usize;
+		let base_x = game.player.0.saturating_sub(len_x / 2);
+		let base_y = game.player.1.saturating_sub(len_y / 2);
+		Viewport {
+			base_x,
+			base_y,
+			len_x,
+			len_y,
+		}
+	}
+
+	pub fn center_around_player(&mut self, game: &game::Game) {
+		self.base_x = game
+			.player
+			.0
+			.saturating_sub(self.len_x / 2)
+			.min(game.size_x().saturating_sub(self.len_x));
+		self.base_y = game
+			.player
+			.1
+			.saturating_sub(self.len_y / 2)
+			.min(game.size_y().saturating_sub(self.len_y));
+	}
+
+	pub fn resize(&mut self, args: piston_window::ResizeArgs) {
+		self.len_x = (args.window_size[1] / TILE_SIDE) as usize;
+		self.len_y = (args.window_size[0] / TILE_SIDE) as usize;
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn messages_are_forgotten_after_a_while() {
+		let mut messages = Messages::default();
+		messages.push("first");
+		messages.update(MESSAGE_DURATION - 1.0);
+		messages.push("second");
+		messages.update(1.0);
+		assert_eq!(messages.lines.iter().map(|(text, _)| text.as_str()).collect::<Vec<_>>(), ["second"]);
+		messages.update(MESSAGE_DURATION);
+		assert!(messages.is_empty());
+	}
+
+	#[test]
+	fn only_the_most_recent_messages_are_kept() {
+		let mut messages = Messages::default();
+		for i in 0..MAX_MESSAGES + 2 {
+			messages.push(format!("{i}"));
+		}
+		assert_eq!(messages.lines.len(), MAX_MESSAGES);
+		assert_eq!(messages.lines.front().unwrap().0, "2");
+	}
+}